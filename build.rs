@@ -0,0 +1,44 @@
+//! Compiles the Cap'n Proto schemas under `schema/` into generated Rust
+//! (emitted to `$OUT_DIR`), which `src/daemon/ipc/capnp_codec.rs` pulls in
+//! via `include!`. Only runs when the `capnp-ipc` feature is enabled, since
+//! most installs never need the `capnp` schema compiler just to talk to the
+//! daemon over the default JSON framing.
+//!
+//! This crate's Cargo.toml (not present in this checkout) is expected to
+//! declare:
+//!   [build-dependencies]
+//!   capnpc = "0.19"
+//!   [dependencies]
+//!   capnp = "0.19"
+//!   [features]
+//!   capnp-ipc = []
+
+fn main() {
+    if std::env::var_os("CARGO_FEATURE_CAPNP_IPC").is_none() {
+        return;
+    }
+
+    let schema_dir = std::path::Path::new("schema");
+    println!("cargo:rerun-if-changed={}", schema_dir.display());
+
+    let mut command = capnpc::CompilerCommand::new();
+    command.src_prefix(schema_dir);
+
+    for entry in std::fs::read_dir(schema_dir).expect("schema/ directory must exist") {
+        let entry = entry.expect("failed to read a schema/ directory entry");
+        let path = entry.path();
+
+        let is_hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.'));
+        let is_capnp = path.extension().and_then(|ext| ext.to_str()) == Some("capnp");
+        if is_hidden || !is_capnp {
+            continue;
+        }
+
+        command.file(&path);
+    }
+
+    command.run().expect("failed to compile Cap'n Proto schemas");
+}
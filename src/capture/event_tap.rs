@@ -1,4 +1,4 @@
-use super::KeyEvent;
+use super::{KeyEvent, Layout};
 use chrono::Utc;
 use std::sync::mpsc::Sender;
 use thiserror::Error;
@@ -55,9 +55,9 @@ pub fn request_accessibility() -> Result<(), CaptureError> {
     Err(CaptureError::PlatformNotSupported)
 }
 
-/// Start capturing keyboard events
+/// Start capturing keyboard events, decoding keycodes under the given layout
 #[cfg(target_os = "macos")]
-pub fn start_capture(tx: Sender<KeyEvent>) -> Result<(), CaptureError> {
+pub fn start_capture(tx: Sender<KeyEvent>, layout: Layout) -> Result<(), CaptureError> {
     use core_foundation::runloop::{kCFRunLoopCommonModes, CFRunLoop};
     use core_graphics::event::{
         CGEvent, CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement,
@@ -81,7 +81,7 @@ pub fn start_capture(tx: Sender<KeyEvent>) -> Result<(), CaptureError> {
             let flags = event.get_flags().bits();
 
             // Create KeyEvent
-            let key_event = KeyEvent::new(keycode, Utc::now(), flags);
+            let key_event = KeyEvent::new(keycode, Utc::now(), flags, &layout);
 
             // Send to channel (ignore errors if receiver is dropped)
             let _ = tx.send(key_event);
@@ -109,7 +109,7 @@ pub fn start_capture(tx: Sender<KeyEvent>) -> Result<(), CaptureError> {
 }
 
 #[cfg(not(target_os = "macos"))]
-pub fn start_capture(_tx: Sender<KeyEvent>) -> Result<(), CaptureError> {
+pub fn start_capture(_tx: Sender<KeyEvent>, _layout: Layout) -> Result<(), CaptureError> {
     Err(CaptureError::PlatformNotSupported)
 }
 
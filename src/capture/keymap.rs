@@ -74,64 +74,30 @@ pub enum SpecialKey {
     PageDown,
 }
 
+/// A keyboard layout, used to decode a physical keycode into the character it
+/// produces. Arrows, modifiers, special and function keys are physical and
+/// shared across layouts; only the alphabetic character positions differ.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Layout {
+    Qwerty,
+    Dvorak,
+    Colemak,
+    Custom(HashMap<u16, char>),
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::Qwerty
+    }
+}
+
 // macOS keycode mapping based on Carbon.h and actual testing
 lazy_static! {
-    pub static ref KEYCODE_MAP: HashMap<u16, KeyType> = {
+    /// Physical (non-remappable) keys: arrows, specials, function keys, modifiers.
+    /// These keycodes are excluded from `*_CHARS` below and never change with layout.
+    static ref PHYSICAL_KEYCODE_MAP: HashMap<u16, KeyType> = {
         let mut m = HashMap::new();
 
-        // Letters (QWERTY layout)
-        m.insert(0, KeyType::Character('a'));
-        m.insert(1, KeyType::Character('s'));
-        m.insert(2, KeyType::Character('d'));
-        m.insert(3, KeyType::Character('f'));
-        m.insert(4, KeyType::Character('h'));
-        m.insert(5, KeyType::Character('g'));
-        m.insert(6, KeyType::Character('z'));
-        m.insert(7, KeyType::Character('x'));
-        m.insert(8, KeyType::Character('c'));
-        m.insert(9, KeyType::Character('v'));
-        m.insert(11, KeyType::Character('b'));
-        m.insert(12, KeyType::Character('q'));
-        m.insert(13, KeyType::Character('w'));
-        m.insert(14, KeyType::Character('e'));
-        m.insert(15, KeyType::Character('r'));
-        m.insert(16, KeyType::Character('y'));
-        m.insert(17, KeyType::Character('t'));
-        m.insert(31, KeyType::Character('o'));
-        m.insert(32, KeyType::Character('u'));
-        m.insert(34, KeyType::Character('i'));
-        m.insert(35, KeyType::Character('p'));
-        m.insert(37, KeyType::Character('l'));
-        m.insert(38, KeyType::Character('j'));
-        m.insert(40, KeyType::Character('k'));
-        m.insert(45, KeyType::Character('n'));
-        m.insert(46, KeyType::Character('m'));
-
-        // Numbers
-        m.insert(18, KeyType::Character('1'));
-        m.insert(19, KeyType::Character('2'));
-        m.insert(20, KeyType::Character('3'));
-        m.insert(21, KeyType::Character('4'));
-        m.insert(23, KeyType::Character('5'));
-        m.insert(22, KeyType::Character('6'));
-        m.insert(26, KeyType::Character('7'));
-        m.insert(28, KeyType::Character('8'));
-        m.insert(25, KeyType::Character('9'));
-        m.insert(29, KeyType::Character('0'));
-
-        // Symbols
-        m.insert(27, KeyType::Character('-'));
-        m.insert(24, KeyType::Character('='));
-        m.insert(33, KeyType::Character('['));
-        m.insert(30, KeyType::Character(']'));
-        m.insert(41, KeyType::Character(';'));
-        m.insert(39, KeyType::Character('\''));
-        m.insert(42, KeyType::Character('\\'));
-        m.insert(43, KeyType::Character(','));
-        m.insert(47, KeyType::Character('.'));
-        m.insert(44, KeyType::Character('/'));
-        m.insert(50, KeyType::Character('`'));
-
         // Arrow keys
         m.insert(123, KeyType::Arrow(ArrowDirection::Left));
         m.insert(124, KeyType::Arrow(ArrowDirection::Right));
@@ -178,14 +144,148 @@ lazy_static! {
 
         m
     };
+
+    /// keycode -> QWERTY character. The reference physical-position table every
+    /// other layout's substitution is defined against.
+    static ref QWERTY_CHARS: HashMap<u16, char> = {
+        let mut m = HashMap::new();
+
+        // Letters (QWERTY layout)
+        m.insert(0, 'a');
+        m.insert(1, 's');
+        m.insert(2, 'd');
+        m.insert(3, 'f');
+        m.insert(4, 'h');
+        m.insert(5, 'g');
+        m.insert(6, 'z');
+        m.insert(7, 'x');
+        m.insert(8, 'c');
+        m.insert(9, 'v');
+        m.insert(11, 'b');
+        m.insert(12, 'q');
+        m.insert(13, 'w');
+        m.insert(14, 'e');
+        m.insert(15, 'r');
+        m.insert(16, 'y');
+        m.insert(17, 't');
+        m.insert(31, 'o');
+        m.insert(32, 'u');
+        m.insert(34, 'i');
+        m.insert(35, 'p');
+        m.insert(37, 'l');
+        m.insert(38, 'j');
+        m.insert(40, 'k');
+        m.insert(45, 'n');
+        m.insert(46, 'm');
+
+        // Numbers
+        m.insert(18, '1');
+        m.insert(19, '2');
+        m.insert(20, '3');
+        m.insert(21, '4');
+        m.insert(23, '5');
+        m.insert(22, '6');
+        m.insert(26, '7');
+        m.insert(28, '8');
+        m.insert(25, '9');
+        m.insert(29, '0');
+
+        // Symbols
+        m.insert(27, '-');
+        m.insert(24, '=');
+        m.insert(33, '[');
+        m.insert(30, ']');
+        m.insert(41, ';');
+        m.insert(39, '\'');
+        m.insert(42, '\\');
+        m.insert(43, ',');
+        m.insert(47, '.');
+        m.insert(44, '/');
+        m.insert(50, '`');
+
+        m
+    };
+
+    /// QWERTY keycode -> Dvorak character at that physical position. Keyed by
+    /// keycode (not QWERTY character): Dvorak moves several letters onto
+    /// keycodes that are punctuation under QWERTY (e.g. keycode 41, QWERTY
+    /// `;`, types `s` in Dvorak), so a char-keyed substitution can't express
+    /// those moves and silently drops the letters that land there.
+    static ref QWERTY_TO_DVORAK: HashMap<u16, char> = {
+        [
+            // Top row: q w e r t y u i o p [ ]
+            (12, '\''), (13, ','), (14, '.'), (15, 'p'), (17, 'y'), (16, 'f'),
+            (32, 'g'), (34, 'c'), (31, 'r'), (35, 'l'), (33, '/'), (30, '='),
+            // Home row: a s d f g h j k l ; '
+            (0, 'a'), (1, 'o'), (2, 'e'), (3, 'u'), (5, 'i'), (4, 'd'),
+            (38, 'h'), (40, 't'), (37, 'n'), (41, 's'), (39, '-'),
+            // Bottom row: z x c v b n m , . /
+            (6, ';'), (7, 'q'), (8, 'j'), (9, 'k'), (11, 'x'), (45, 'b'),
+            (46, 'm'), (43, 'w'), (47, 'v'), (44, 'z'),
+        ]
+        .into_iter()
+        .collect()
+    };
+
+    /// QWERTY keycode -> Colemak character at that physical position. Keyed
+    /// by keycode for the same reason as `QWERTY_TO_DVORAK`: Colemak moves
+    /// `o` onto keycode 41 (QWERTY `;`), a letter landing on a key that is
+    /// punctuation under QWERTY.
+    static ref QWERTY_TO_COLEMAK: HashMap<u16, char> = {
+        [
+            // Top row: q w e r t y u i o p [ ]
+            (12, 'q'), (13, 'w'), (14, 'f'), (15, 'p'), (17, 'g'), (16, 'j'),
+            (32, 'l'), (34, 'u'), (31, 'y'), (35, ';'), (33, '['), (30, ']'),
+            // Home row: a s d f g h j k l ; '
+            (0, 'a'), (1, 'r'), (2, 's'), (3, 't'), (5, 'd'), (4, 'h'),
+            (38, 'n'), (40, 'e'), (37, 'i'), (41, 'o'), (39, '\''),
+            // Bottom row: z x c v b n m , . /
+            (6, 'z'), (7, 'x'), (8, 'c'), (9, 'v'), (11, 'b'), (45, 'k'),
+            (46, 'm'), (43, ','), (47, '.'), (44, '/'),
+        ]
+        .into_iter()
+        .collect()
+    };
+
+    static ref DVORAK_CHARS: HashMap<u16, char> = remap_chars(&QWERTY_CHARS, &QWERTY_TO_DVORAK);
+    static ref COLEMAK_CHARS: HashMap<u16, char> = remap_chars(&QWERTY_CHARS, &QWERTY_TO_COLEMAK);
 }
 
-/// Convert macOS keycode to KeyType
-pub fn keycode_to_key(keycode: u16) -> KeyType {
-    KEYCODE_MAP
-        .get(&keycode)
-        .cloned()
-        .unwrap_or(KeyType::Unknown(keycode))
+fn remap_chars(base: &HashMap<u16, char>, substitution: &HashMap<u16, char>) -> HashMap<u16, char> {
+    base.iter()
+        .map(|(&code, &ch)| (code, *substitution.get(&code).unwrap_or(&ch)))
+        .collect()
+}
+
+/// Convert a macOS keycode to a `KeyType`, decoding character positions under
+/// the given layout. Arrows, modifiers, specials and function keys are the
+/// same physical keys regardless of layout.
+pub fn keycode_to_key(keycode: u16, layout: &Layout) -> KeyType {
+    if let Some(key) = PHYSICAL_KEYCODE_MAP.get(&keycode) {
+        return key.clone();
+    }
+
+    let ch = match layout {
+        Layout::Qwerty => QWERTY_CHARS.get(&keycode).copied(),
+        Layout::Dvorak => DVORAK_CHARS.get(&keycode).copied(),
+        Layout::Colemak => COLEMAK_CHARS.get(&keycode).copied(),
+        Layout::Custom(map) => map.get(&keycode).copied(),
+    };
+
+    ch.map(KeyType::Character).unwrap_or(KeyType::Unknown(keycode))
+}
+
+/// Check that a layout's character map covers every printable letter (a-z),
+/// as required of `Layout::Custom` maps before they're saved to config.
+pub fn layout_covers_printable_letters(layout: &Layout) -> bool {
+    let mapped: std::collections::HashSet<char> = match layout {
+        Layout::Qwerty => QWERTY_CHARS.values().copied().collect(),
+        Layout::Dvorak => DVORAK_CHARS.values().copied().collect(),
+        Layout::Colemak => COLEMAK_CHARS.values().copied().collect(),
+        Layout::Custom(map) => map.values().copied().collect(),
+    };
+
+    "abcdefghijklmnopqrstuvwxyz".chars().all(|c| mapped.contains(&c))
 }
 
 #[cfg(test)]
@@ -193,75 +293,87 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_letter_keycodes() {
-        assert_eq!(keycode_to_key(0), KeyType::Character('a'));
-        assert_eq!(keycode_to_key(1), KeyType::Character('s'));
-        assert_eq!(keycode_to_key(2), KeyType::Character('d'));
-        assert_eq!(keycode_to_key(3), KeyType::Character('f'));
+    fn test_letter_keycodes_qwerty() {
+        assert_eq!(keycode_to_key(0, &Layout::Qwerty), KeyType::Character('a'));
+        assert_eq!(keycode_to_key(1, &Layout::Qwerty), KeyType::Character('s'));
+        assert_eq!(keycode_to_key(2, &Layout::Qwerty), KeyType::Character('d'));
+        assert_eq!(keycode_to_key(3, &Layout::Qwerty), KeyType::Character('f'));
     }
 
     #[test]
     fn test_number_keycodes() {
-        assert_eq!(keycode_to_key(18), KeyType::Character('1'));
-        assert_eq!(keycode_to_key(19), KeyType::Character('2'));
-        assert_eq!(keycode_to_key(20), KeyType::Character('3'));
+        assert_eq!(keycode_to_key(18, &Layout::Qwerty), KeyType::Character('1'));
+        assert_eq!(keycode_to_key(19, &Layout::Qwerty), KeyType::Character('2'));
+        assert_eq!(keycode_to_key(20, &Layout::Qwerty), KeyType::Character('3'));
     }
 
     #[test]
-    fn test_arrow_keycodes() {
-        assert_eq!(keycode_to_key(123), KeyType::Arrow(ArrowDirection::Left));
-        assert_eq!(keycode_to_key(124), KeyType::Arrow(ArrowDirection::Right));
-        assert_eq!(keycode_to_key(125), KeyType::Arrow(ArrowDirection::Down));
-        assert_eq!(keycode_to_key(126), KeyType::Arrow(ArrowDirection::Up));
+    fn test_arrow_keycodes_are_layout_independent() {
+        for layout in [Layout::Qwerty, Layout::Dvorak, Layout::Colemak] {
+            assert_eq!(keycode_to_key(123, &layout), KeyType::Arrow(ArrowDirection::Left));
+            assert_eq!(keycode_to_key(124, &layout), KeyType::Arrow(ArrowDirection::Right));
+            assert_eq!(keycode_to_key(125, &layout), KeyType::Arrow(ArrowDirection::Down));
+            assert_eq!(keycode_to_key(126, &layout), KeyType::Arrow(ArrowDirection::Up));
+        }
     }
 
     #[test]
     fn test_special_keycodes() {
-        assert_eq!(keycode_to_key(36), KeyType::Special(SpecialKey::Return));
-        assert_eq!(keycode_to_key(48), KeyType::Special(SpecialKey::Tab));
-        assert_eq!(keycode_to_key(49), KeyType::Special(SpecialKey::Space));
-        assert_eq!(keycode_to_key(53), KeyType::Special(SpecialKey::Escape));
+        assert_eq!(keycode_to_key(36, &Layout::Qwerty), KeyType::Special(SpecialKey::Return));
+        assert_eq!(keycode_to_key(48, &Layout::Qwerty), KeyType::Special(SpecialKey::Tab));
+        assert_eq!(keycode_to_key(49, &Layout::Qwerty), KeyType::Special(SpecialKey::Space));
+        assert_eq!(keycode_to_key(53, &Layout::Qwerty), KeyType::Special(SpecialKey::Escape));
     }
 
     #[test]
     fn test_function_keycodes() {
-        assert_eq!(keycode_to_key(122), KeyType::Function(1));
-        assert_eq!(keycode_to_key(120), KeyType::Function(2));
-        assert_eq!(keycode_to_key(111), KeyType::Function(12));
+        assert_eq!(keycode_to_key(122, &Layout::Qwerty), KeyType::Function(1));
+        assert_eq!(keycode_to_key(120, &Layout::Qwerty), KeyType::Function(2));
+        assert_eq!(keycode_to_key(111, &Layout::Qwerty), KeyType::Function(12));
     }
 
     #[test]
     fn test_unknown_keycode() {
-        assert_eq!(keycode_to_key(999), KeyType::Unknown(999));
+        assert_eq!(keycode_to_key(999, &Layout::Qwerty), KeyType::Unknown(999));
     }
 
     #[test]
     fn test_all_printable_chars_mapped() {
-        let letters = "asdfghjklqwertyuiopzxcvbnm";
-        let mapped: Vec<char> = KEYCODE_MAP
-            .values()
-            .filter_map(|k| match k {
-                KeyType::Character(c) => Some(*c),
-                _ => None,
-            })
-            .collect();
-
-        for letter in letters.chars() {
+        for layout in [Layout::Qwerty, Layout::Dvorak, Layout::Colemak] {
             assert!(
-                mapped.contains(&letter),
-                "Letter '{}' not found in keymap",
-                letter
+                layout_covers_printable_letters(&layout),
+                "{:?} does not cover every printable letter",
+                layout
             );
         }
     }
 
+    #[test]
+    fn test_dvorak_remaps_qwerty_positions() {
+        // The QWERTY 'e' key (keycode 14) types '.' under Dvorak.
+        assert_eq!(keycode_to_key(14, &Layout::Dvorak), KeyType::Character('.'));
+        // The QWERTY 'a' key (keycode 0) stays 'a' under Dvorak.
+        assert_eq!(keycode_to_key(0, &Layout::Dvorak), KeyType::Character('a'));
+    }
+
+    #[test]
+    fn test_colemak_remaps_qwerty_positions() {
+        // The QWERTY 'f' key (keycode 3) types 't' under Colemak.
+        assert_eq!(keycode_to_key(3, &Layout::Colemak), KeyType::Character('t'));
+    }
+
+    #[test]
+    fn test_custom_layout_rejects_incomplete_map() {
+        let mut map = HashMap::new();
+        map.insert(0, 'a');
+        let layout = Layout::Custom(map);
+        assert!(!layout_covers_printable_letters(&layout));
+    }
+
     #[test]
     fn test_key_type_name() {
         assert_eq!(KeyType::Character('a').name(), "a");
-        assert_eq!(
-            KeyType::Arrow(ArrowDirection::Left).name(),
-            "arrow:left"
-        );
+        assert_eq!(KeyType::Arrow(ArrowDirection::Left).name(), "arrow:left");
         assert_eq!(KeyType::Function(5).name(), "F5");
     }
 }
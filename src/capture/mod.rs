@@ -3,7 +3,11 @@ mod keymap;
 pub mod window;
 
 pub use event_tap::{start_capture, CaptureError};
-pub use keymap::{keycode_to_key, ArrowDirection, KeyType, ModifierKey, SpecialKey};
+pub use keymap::{
+    keycode_to_key, layout_covers_printable_letters, ArrowDirection, KeyType, Layout, ModifierKey,
+    SpecialKey,
+};
+pub use window::{get_active_window, WindowInfo};
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -19,10 +23,10 @@ pub struct KeyEvent {
 }
 
 impl KeyEvent {
-    pub fn new(keycode: u16, timestamp: DateTime<Utc>, flags: u64) -> Self {
+    pub fn new(keycode: u16, timestamp: DateTime<Utc>, flags: u64, layout: &Layout) -> Self {
         Self {
             keycode,
-            key_type: keycode_to_key(keycode),
+            key_type: keycode_to_key(keycode, layout),
             timestamp,
             modifiers: Modifiers::from_flags(flags),
         }
@@ -36,27 +40,111 @@ pub struct Modifiers {
     pub control: bool,
     pub option: bool,
     pub command: bool,
+    pub caps_lock: bool,
+    pub function: bool,
 }
 
 impl Modifiers {
     pub fn from_flags(flags: u64) -> Self {
         // macOS CGEventFlags values
+        const CAPS_LOCK: u64 = 0x00010000;
         const SHIFT: u64 = 0x00020000;
         const CONTROL: u64 = 0x00040000;
         const OPTION: u64 = 0x00080000;
         const COMMAND: u64 = 0x00100000;
+        const FUNCTION: u64 = 0x00800000;
 
         Self {
             shift: (flags & SHIFT) != 0,
             control: (flags & CONTROL) != 0,
             option: (flags & OPTION) != 0,
             command: (flags & COMMAND) != 0,
+            caps_lock: (flags & CAPS_LOCK) != 0,
+            function: (flags & FUNCTION) != 0,
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        !self.shift && !self.control && !self.option && !self.command
+        !self.shift && !self.control && !self.option && !self.command && !self.caps_lock && !self.function
     }
+
+    /// Held modifiers in a canonical `cmd, ctrl, opt, shift, fn, capslock` order,
+    /// used to build a [`Chord`]'s combo rendering.
+    pub fn active(&self) -> Vec<ModifierKey> {
+        let mut active = Vec::new();
+        if self.command {
+            active.push(ModifierKey::Command);
+        }
+        if self.control {
+            active.push(ModifierKey::Control);
+        }
+        if self.option {
+            active.push(ModifierKey::Option);
+        }
+        if self.shift {
+            active.push(ModifierKey::Shift);
+        }
+        if self.function {
+            active.push(ModifierKey::Function);
+        }
+        if self.caps_lock {
+            active.push(ModifierKey::CapsLock);
+        }
+        active
+    }
+}
+
+/// A non-modifier key pressed while one or more modifiers are held, e.g. `cmd+shift+z`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chord {
+    pub modifiers: Vec<ModifierKey>,
+    pub key: KeyType,
+}
+
+impl Chord {
+    /// Canonical rendering used as the `shortcuts` storage key, e.g. `cmd+shift+z`.
+    pub fn combo(&self) -> String {
+        let mut parts: Vec<String> = self
+            .modifiers
+            .iter()
+            .map(|m| modifier_label(*m).to_string())
+            .collect();
+        parts.push(self.key.name());
+        parts.join("+")
+    }
+}
+
+fn modifier_label(modifier: ModifierKey) -> &'static str {
+    match modifier {
+        ModifierKey::Shift => "shift",
+        ModifierKey::Control => "ctrl",
+        ModifierKey::Option => "opt",
+        ModifierKey::Command => "cmd",
+        ModifierKey::CapsLock => "capslock",
+        ModifierKey::Function => "fn",
+    }
+}
+
+/// macOS keycodes for bare modifier keys (shift/control/option/command/capslock/fn).
+/// A KeyDown at one of these codes is a modifier tap, not part of a chord.
+const MODIFIER_KEYCODES: std::ops::RangeInclusive<u16> = 54..=63;
+
+/// Decide whether a key event represents a chord (a non-modifier key pressed
+/// while control/option/command is held), as opposed to a bare keystroke or a
+/// lone modifier tap.
+pub fn chord_for(event: &KeyEvent) -> Option<Chord> {
+    if MODIFIER_KEYCODES.contains(&event.keycode) {
+        return None;
+    }
+
+    if !(event.modifiers.control || event.modifiers.option || event.modifiers.command) {
+        return None;
+    }
+
+    Some(Chord {
+        modifiers: event.modifiers.active(),
+        key: event.key_type.clone(),
+    })
 }
 
 impl fmt::Display for Modifiers {
@@ -99,7 +187,45 @@ mod tests {
             control: true,
             option: false,
             command: false,
+            caps_lock: false,
+            function: false,
         };
         assert_eq!(mods.to_string(), "Ctrl+Shift");
     }
+
+    fn event_with(keycode: u16, key_type: KeyType, flags: u64) -> KeyEvent {
+        KeyEvent {
+            keycode,
+            key_type,
+            timestamp: Utc::now(),
+            modifiers: Modifiers::from_flags(flags),
+        }
+    }
+
+    #[test]
+    fn test_chord_detected_for_modified_non_modifier_key() {
+        // cmd+shift+z
+        let event = event_with(6, KeyType::Character('z'), 0x00120000);
+        let chord = chord_for(&event).expect("expected a chord");
+        assert_eq!(chord.combo(), "cmd+shift+z");
+    }
+
+    #[test]
+    fn test_bare_modifier_tap_is_not_a_chord() {
+        // Right Command key itself, keycode 54, with its own flag set
+        let event = event_with(54, KeyType::Modifier(ModifierKey::Command), 0x00100000);
+        assert!(chord_for(&event).is_none());
+    }
+
+    #[test]
+    fn test_plain_keystroke_without_modifiers_is_not_a_chord() {
+        let event = event_with(0, KeyType::Character('a'), 0);
+        assert!(chord_for(&event).is_none());
+    }
+
+    #[test]
+    fn test_shift_alone_is_not_a_chord() {
+        let event = event_with(1, KeyType::Character('s'), 0x00020000);
+        assert!(chord_for(&event).is_none());
+    }
 }
@@ -1,6 +1,8 @@
-//! Window context tracking for macOS
+//! Window context tracking
 //!
-//! Tracks the currently active window and process information.
+//! Tracks the currently active window and process information. macOS and
+//! Linux each get their own backend; everywhere else `get_active_window`
+//! simply returns `None`.
 
 use serde::{Deserialize, Serialize};
 
@@ -31,7 +33,143 @@ pub fn get_active_window() -> Option<WindowInfo> {
     })
 }
 
-#[cfg(not(target_os = "macos"))]
+/// X11 backend, used on Linux.
+#[cfg(target_os = "linux")]
+mod x11 {
+    use super::{WindowGeometry, WindowInfo};
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+    use x11rb::rust_connection::RustConnection;
+
+    /// Holds the X11 connection and the atoms we query repeatedly, so a
+    /// caller can poll for the active window more than once without
+    /// re-interning atoms or re-connecting each time.
+    ///
+    /// Implements `AsRawFd` so the capture loop can `select`/poll this
+    /// connection's socket alongside its own timeout, the way x11rb expects
+    /// to be driven from an external event loop instead of busy-polling.
+    pub struct X11WindowWatcher {
+        conn: RustConnection,
+        root: u32,
+        net_active_window: u32,
+        net_wm_name: u32,
+        utf8_string: u32,
+        wm_class: u32,
+    }
+
+    impl X11WindowWatcher {
+        pub fn connect() -> Option<Self> {
+            let (conn, screen_num) = RustConnection::connect(None).ok()?;
+            let root = conn.setup().roots[screen_num].root;
+            Some(Self {
+                net_active_window: intern_atom(&conn, "_NET_ACTIVE_WINDOW")?,
+                net_wm_name: intern_atom(&conn, "_NET_WM_NAME")?,
+                utf8_string: intern_atom(&conn, "UTF8_STRING")?,
+                wm_class: intern_atom(&conn, "WM_CLASS")?,
+                conn,
+                root,
+            })
+        }
+
+        pub fn active_window(&self) -> Option<WindowInfo> {
+            let active = self.active_window_id()?;
+            Some(WindowInfo {
+                process_name: self.wm_class(active).unwrap_or_else(|| "Unknown".to_string()),
+                window_title: self
+                    .text_property(active, self.net_wm_name, self.utf8_string)
+                    .unwrap_or_else(|| "Unknown".to_string()),
+                geometry: self.geometry(active),
+            })
+        }
+
+        fn active_window_id(&self) -> Option<u32> {
+            let reply = self
+                .conn
+                .get_property(false, self.root, self.net_active_window, AtomEnum::WINDOW, 0, 1)
+                .ok()?
+                .reply()
+                .ok()?;
+            reply.value32()?.next()
+        }
+
+        fn text_property(&self, window: u32, property: u32, prop_type: u32) -> Option<String> {
+            let reply = self
+                .conn
+                .get_property(false, window, property, prop_type, 0, u32::MAX)
+                .ok()?
+                .reply()
+                .ok()?;
+            String::from_utf8(reply.value).ok()
+        }
+
+        /// `WM_CLASS` is two NUL-terminated strings back to back: the
+        /// instance name, then the class name. The class name is the stable
+        /// per-application identifier we want as `process_name`.
+        fn wm_class(&self, window: u32) -> Option<String> {
+            let reply = self
+                .conn
+                .get_property(false, window, self.wm_class, AtomEnum::STRING, 0, u32::MAX)
+                .ok()?
+                .reply()
+                .ok()?;
+            let text = String::from_utf8(reply.value).ok()?;
+            text.split('\0').nth(1).filter(|s| !s.is_empty()).map(str::to_string)
+        }
+
+        fn geometry(&self, window: u32) -> Option<WindowGeometry> {
+            let geom = self.conn.get_geometry(window).ok()?.reply().ok()?;
+            let translated = self
+                .conn
+                .translate_coordinates(window, self.root, 0, 0)
+                .ok()?
+                .reply()
+                .ok()?;
+            Some(WindowGeometry {
+                x: translated.dst_x as i32,
+                y: translated.dst_y as i32,
+                width: geom.width as u32,
+                height: geom.height as u32,
+            })
+        }
+    }
+
+    impl AsRawFd for X11WindowWatcher {
+        fn as_raw_fd(&self) -> RawFd {
+            self.conn.as_raw_fd()
+        }
+    }
+
+    fn intern_atom(conn: &RustConnection, name: &str) -> Option<u32> {
+        Some(conn.intern_atom(false, name.as_bytes()).ok()?.reply().ok()?.atom)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use x11::X11WindowWatcher;
+
+#[cfg(target_os = "linux")]
+thread_local! {
+    /// One `X11WindowWatcher` per capture thread, reused across polls instead
+    /// of opening and tearing down a fresh X11 connection on every call —
+    /// see `X11WindowWatcher`'s doc comment on why it's held rather than
+    /// reconnected.
+    static ACTIVE_WINDOW_WATCHER: std::cell::RefCell<Option<X11WindowWatcher>> =
+        std::cell::RefCell::new(None);
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_active_window() -> Option<WindowInfo> {
+    ACTIVE_WINDOW_WATCHER.with(|cell| {
+        let mut watcher = cell.borrow_mut();
+        if watcher.is_none() {
+            *watcher = X11WindowWatcher::connect();
+        }
+        watcher.as_ref()?.active_window()
+    })
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
 pub fn get_active_window() -> Option<WindowInfo> {
     None
 }
@@ -78,9 +78,21 @@ pub enum Commands {
         #[arg(short, long)]
         output: PathBuf,
 
-        /// Format: json, csv
+        /// Format: json, csv, dot
         #[arg(long, default_value = "json")]
         format: String,
+
+        /// Time range for `dot` export: today, week, month, all
+        #[arg(long, default_value = "all")]
+        range: String,
+
+        /// Drop `dot` edges below this transition count
+        #[arg(long, default_value = "0")]
+        min_weight: u64,
+
+        /// Emit an undirected `graph` instead of a `digraph`, merging (a,b)/(b,a)
+        #[arg(long)]
+        undirected: bool,
     },
 
     /// Manage configuration
@@ -95,11 +107,22 @@ pub enum ConfigAction {
     /// Open config in $EDITOR
     Edit,
     /// Show current config
-    Show,
+    Show {
+        /// Render the effective config with this profile's overrides applied
+        #[arg(long)]
+        profile: Option<String>,
+    },
     /// Reset to defaults
     Reset,
     /// Show config file path
     Path,
+    /// Set the keyboard layout used to decode keycodes into characters
+    SetLayout {
+        /// qwerty, dvorak, or colemak
+        layout: String,
+    },
+    /// List defined profiles and their match rules
+    Profiles,
 }
 
 /// Query subcommands (inspired by selfspy's selfstats)
@@ -152,6 +175,22 @@ pub enum QueryAction {
         #[arg(long)]
         human_readable: bool,
     },
+
+    /// Run a query expression, e.g. `SELECT keys BY process WHERE process ~ "Code" LIMIT 10`
+    Run {
+        /// The query expression to parse and execute
+        expr: String,
+    },
+
+    /// Show most-used modifier+key shortcuts, overall or for one process
+    Shortcuts {
+        /// Restrict to a single process
+        #[arg(short, long)]
+        process: Option<String>,
+
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
 }
 
 #[cfg(test)]
@@ -0,0 +1,105 @@
+//! Clock abstraction so time-dependent logic (milestone timestamps, hourly
+//! bucket rollover, APM windows) can be driven deterministically in tests
+//! instead of reaching for the real wall clock and monotonic clock inline.
+
+use chrono::{DateTime, Utc};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A source of wall-clock and monotonic time. Implementations must be
+/// `Send + Sync` so an `Arc<dyn Clocks>` can be shared across the daemon's
+/// capture thread and main loop.
+pub trait Clocks: Send + Sync {
+    /// Current wall-clock time, used for timestamps (milestone `reached_at`,
+    /// hourly buckets, session start/end).
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Current point on the monotonic clock, used for elapsed-time
+    /// calculations (APM rolling windows, session duration) that must never
+    /// go backwards even if the wall clock is adjusted.
+    fn monotonic_now(&self) -> Instant;
+}
+
+/// Production clock backed by the real OS wall clock and monotonic clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClocks;
+
+impl Clocks for SystemClocks {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn monotonic_now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+struct SimulatedState {
+    wall_clock: DateTime<Utc>,
+    // `Instant` has no public constructor other than `now()`, so simulated
+    // monotonic time is modeled as an offset from a real instant captured
+    // when the clock was created.
+    monotonic_base: Instant,
+    monotonic_offset: Duration,
+}
+
+/// Test clock whose wall-clock and monotonic time only move when
+/// [`advance`](Self::advance) is called, so rollover/window logic can be
+/// exercised without sleeping in the test thread.
+pub struct SimulatedClocks {
+    state: Mutex<SimulatedState>,
+}
+
+impl SimulatedClocks {
+    /// Start the simulated wall clock at `start`; the simulated monotonic
+    /// clock starts at zero offset.
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            state: Mutex::new(SimulatedState {
+                wall_clock: start,
+                monotonic_base: Instant::now(),
+                monotonic_offset: Duration::ZERO,
+            }),
+        }
+    }
+
+    /// Move both the wall clock and the monotonic clock forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.wall_clock += chrono::Duration::from_std(by).expect("duration fits in chrono::Duration");
+        state.monotonic_offset += by;
+    }
+}
+
+impl Clocks for SimulatedClocks {
+    fn now(&self) -> DateTime<Utc> {
+        self.state.lock().unwrap().wall_clock
+    }
+
+    fn monotonic_now(&self) -> Instant {
+        let state = self.state.lock().unwrap();
+        state.monotonic_base + state.monotonic_offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulated_clocks_advance_moves_both_clocks() {
+        let start = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let clocks = SimulatedClocks::new(start);
+        let initial_monotonic = clocks.monotonic_now();
+
+        clocks.advance(Duration::from_secs(3600));
+
+        assert_eq!(clocks.now(), start + chrono::Duration::hours(1));
+        assert_eq!(
+            clocks.monotonic_now() - initial_monotonic,
+            Duration::from_secs(3600)
+        );
+    }
+}
@@ -1,8 +1,9 @@
 //! Configuration management for kstrk
 
+use crate::capture::{Layout, WindowInfo};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +12,22 @@ pub struct Config {
     pub stats: StatsConfig,
     pub storage: StorageConfig,
     pub heatmap: HeatmapConfig,
+    #[serde(default)]
+    pub keyboard: KeyboardConfig,
+    /// Named `[profile.<name>]` overrides of `capture`, selected per-window
+    /// at capture time. See [`Config::effective_capture`].
+    #[serde(default)]
+    pub profile: HashMap<String, Profile>,
+    /// Profile to fall back to when the active window matches none of
+    /// `profile`'s per-profile rules.
+    #[serde(default)]
+    pub default_profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct KeyboardConfig {
+    #[serde(default)]
+    pub layout: Layout,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +38,46 @@ pub struct CaptureConfig {
     pub ignore_lone_modifiers: bool,
     #[serde(default = "default_gap_threshold")]
     pub token_gap_threshold: u64,
+    /// Like selfspy's `--no-text`: redact the captured process/window
+    /// identifiers instead of storing them, keeping only key counts.
+    #[serde(default)]
+    pub no_text: bool,
+    /// Processes to skip recording entirely (e.g. password managers).
+    #[serde(default)]
+    pub ignored_processes: Vec<String>,
+}
+
+/// A named override of [`CaptureConfig`], selected by matching the active
+/// window's process name / title against `match_process`/`match_title`
+/// (case-insensitive substring match). Any field left `None`/empty falls
+/// back to the base `capture` setting.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Profile {
+    #[serde(default)]
+    pub match_process: Vec<String>,
+    #[serde(default)]
+    pub match_title: Vec<String>,
+    pub token_gap_threshold: Option<u64>,
+    pub no_text: Option<bool>,
+    #[serde(default)]
+    pub ignored_processes: Vec<String>,
+}
+
+impl Profile {
+    /// Whether this profile's match rules select `window`. A profile with
+    /// no rules at all never matches automatically — it's only reachable
+    /// via `default_profile`.
+    fn matches(&self, window: &WindowInfo) -> bool {
+        let process_hit = self
+            .match_process
+            .iter()
+            .any(|pattern| window.process_name.to_lowercase().contains(&pattern.to_lowercase()));
+        let title_hit = self
+            .match_title
+            .iter()
+            .any(|pattern| window.window_title.to_lowercase().contains(&pattern.to_lowercase()));
+        process_hit || title_hit
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +95,50 @@ pub struct StorageConfig {
     pub retention_days: u32,
     #[serde(default = "default_aggregate_days")]
     pub aggregate_after_days: u32,
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+    #[serde(default)]
+    pub snapshots: SnapshotConfig,
+}
+
+/// At-rest encryption of captured process/window text. The passphrase
+/// itself is never stored here — it's read from the `KSTRK_PASSPHRASE`
+/// environment variable at daemon startup, so it doesn't end up in the
+/// config file on disk.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct EncryptionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// Periodic, rotated `SqliteStorage::snapshot_to` exports, for crash and
+/// corruption recovery independent of the live database file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_snapshot_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_snapshot_retain")]
+    pub retain: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: default_snapshot_interval_secs(),
+            retain: default_snapshot_retain(),
+        }
+    }
+}
+
+fn default_snapshot_interval_secs() -> u64 {
+    3600
+}
+
+fn default_snapshot_retain() -> usize {
+    7
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,6 +188,8 @@ impl Default for Config {
                 ignore_keys: default_ignore_keys(),
                 ignore_lone_modifiers: true,
                 token_gap_threshold: default_gap_threshold(),
+                no_text: false,
+                ignored_processes: Vec::new(),
             },
             stats: StatsConfig {
                 apm_window_secs: default_apm_window(),
@@ -96,11 +199,16 @@ impl Default for Config {
                 data_dir: None,
                 retention_days: default_retention_days(),
                 aggregate_after_days: default_aggregate_days(),
+                encryption: EncryptionConfig::default(),
+                snapshots: SnapshotConfig::default(),
             },
             heatmap: HeatmapConfig {
                 color_scheme: default_color_scheme(),
                 show_labels: true,
             },
+            keyboard: KeyboardConfig::default(),
+            profile: HashMap::new(),
+            default_profile: None,
         }
     }
 }
@@ -136,6 +244,12 @@ impl Config {
 
     /// Save configuration to file
     pub fn save(&self) -> Result<(), crate::Error> {
+        if !crate::capture::layout_covers_printable_letters(&self.keyboard.layout) {
+            return Err(crate::Error::Config(
+                "keyboard layout does not map every printable letter (a-z)".to_string(),
+            ));
+        }
+
         if let Some(path) = Self::config_path() {
             if let Some(parent) = path.parent() {
                 std::fs::create_dir_all(parent)?;
@@ -146,6 +260,58 @@ impl Config {
         }
         Ok(())
     }
+
+    /// Pick the active profile for the current window: among `profile`
+    /// entries whose rules match, the one that sorts first by name, or
+    /// `default_profile` if none match. Returns `None` with no `window` and
+    /// no `default_profile` set.
+    ///
+    /// Ties are broken by name (rather than `HashMap` iteration order, which
+    /// is randomized per process) so that which profile wins — and therefore
+    /// whether `no_text` redaction kicks in for an overlapping
+    /// password-manager/banking window — is the same every run.
+    pub fn active_profile(&self, window: Option<&WindowInfo>) -> Option<(&str, &Profile)> {
+        if let Some(window) = window {
+            let mut matching: Vec<&String> = self
+                .profile
+                .iter()
+                .filter(|(_, profile)| profile.matches(window))
+                .map(|(name, _)| name)
+                .collect();
+            matching.sort();
+            if let Some(name) = matching.into_iter().next() {
+                return Some((name.as_str(), &self.profile[name]));
+            }
+        }
+        let name = self.default_profile.as_deref()?;
+        self.profile.get(name).map(|profile| (name, profile))
+    }
+
+    /// The effective `capture` settings once the profile selected for
+    /// `window` (see [`Config::active_profile`]) is layered over the base
+    /// `capture` config. `ignored_processes` is the union of both, the rest
+    /// are simple overrides.
+    pub fn effective_capture(&self, window: Option<&WindowInfo>) -> CaptureConfig {
+        match self.active_profile(window) {
+            Some((_, profile)) => self.effective_capture_for_profile(profile),
+            None => self.capture.clone(),
+        }
+    }
+
+    /// Like [`Config::effective_capture`], but for a profile picked by name
+    /// rather than by matching the active window (e.g. `kstrk config show
+    /// --profile <name>`).
+    pub fn effective_capture_for_profile(&self, profile: &Profile) -> CaptureConfig {
+        let mut capture = self.capture.clone();
+        if let Some(threshold) = profile.token_gap_threshold {
+            capture.token_gap_threshold = threshold;
+        }
+        if let Some(no_text) = profile.no_text {
+            capture.no_text = no_text;
+        }
+        capture.ignored_processes.extend(profile.ignored_processes.iter().cloned());
+        capture
+    }
 }
 
 /// Filter for ignoring certain keys
@@ -194,6 +360,12 @@ mod tests {
         assert!(config.stats.milestones_enabled);
     }
 
+    #[test]
+    fn test_default_keyboard_layout_is_qwerty() {
+        let config = Config::default();
+        assert_eq!(config.keyboard.layout, crate::capture::Layout::Qwerty);
+    }
+
     #[test]
     fn test_config_serialization() {
         let config = Config::default();
@@ -201,4 +373,104 @@ mod tests {
         let deserialized: Config = toml::from_str(&serialized).unwrap();
         assert_eq!(config.capture.token_gap_threshold, deserialized.capture.token_gap_threshold);
     }
+
+    fn window(process: &str, title: &str) -> WindowInfo {
+        WindowInfo {
+            process_name: process.to_string(),
+            window_title: title.to_string(),
+            geometry: None,
+        }
+    }
+
+    #[test]
+    fn test_profile_matches_process_by_case_insensitive_substring() {
+        let mut config = Config::default();
+        config.profile.insert(
+            "banking".to_string(),
+            Profile {
+                match_process: vec!["1Password".to_string()],
+                no_text: Some(true),
+                ..Profile::default()
+            },
+        );
+
+        let effective = config.effective_capture(Some(&window("1password", "Vault")));
+        assert!(effective.no_text);
+    }
+
+    #[test]
+    fn test_profile_overrides_gap_threshold_and_merges_ignored_processes() {
+        let mut config = Config::default();
+        config.capture.ignored_processes = vec!["launchd".to_string()];
+        config.profile.insert(
+            "coding".to_string(),
+            Profile {
+                match_title: vec!["Visual Studio Code".to_string()],
+                token_gap_threshold: Some(2000),
+                ignored_processes: vec!["Spotlight".to_string()],
+                ..Profile::default()
+            },
+        );
+
+        let effective = config.effective_capture(Some(&window("Code", "main.rs - Visual Studio Code")));
+        assert_eq!(effective.token_gap_threshold, 2000);
+        assert_eq!(
+            effective.ignored_processes,
+            vec!["launchd".to_string(), "Spotlight".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_default_profile_applies_when_no_rule_matches() {
+        let mut config = Config::default();
+        config.default_profile = Some("locked_down".to_string());
+        config.profile.insert(
+            "locked_down".to_string(),
+            Profile {
+                no_text: Some(true),
+                ..Profile::default()
+            },
+        );
+
+        let effective = config.effective_capture(Some(&window("Terminal", "zsh")));
+        assert!(effective.no_text);
+    }
+
+    #[test]
+    fn test_overlapping_profile_matches_resolve_deterministically_by_name() {
+        let mut config = Config::default();
+        config.profile.insert(
+            "zebra".to_string(),
+            Profile {
+                match_process: vec!["1Password".to_string()],
+                no_text: Some(false),
+                ..Profile::default()
+            },
+        );
+        config.profile.insert(
+            "banking".to_string(),
+            Profile {
+                match_process: vec!["1Password".to_string()],
+                no_text: Some(true),
+                ..Profile::default()
+            },
+        );
+
+        // Both profiles match; "banking" must win every time because it
+        // sorts first, regardless of HashMap iteration order.
+        for _ in 0..20 {
+            let (name, _) = config
+                .active_profile(Some(&window("1password", "Vault")))
+                .expect("expected a matching profile");
+            assert_eq!(name, "banking");
+        }
+    }
+
+    #[test]
+    fn test_no_window_and_no_default_profile_leaves_capture_unchanged() {
+        let config = Config::default();
+        let effective = config.effective_capture(None);
+        assert_eq!(effective.token_gap_threshold, config.capture.token_gap_threshold);
+        assert!(!effective.no_text);
+    }
 }
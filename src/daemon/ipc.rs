@@ -1,8 +1,48 @@
 //! IPC module for daemon communication
+//!
+//! The CLI talks to the running daemon over a Unix-domain socket under
+//! `Config::data_dir()`. Every connection starts with a handshake: the
+//! client sends a [`Hello`] naming the protocol it speaks and the wire
+//! [`WireFormat`]s it can encode/decode, and the daemon replies
+//! [`HelloAck::Ok`] (naming the format it picked) or a nack
+//! ([`HelloAck::Error`]) before either side exchanges a real
+//! [`Request`]/[`Response`] — so a `chain_name`/`protocol_version` mismatch
+//! between an old CLI and a newer daemon (or vice versa) fails with a clear
+//! error instead of deserializing garbage.
+//!
+//! The handshake frames ([`Hello`]/[`HelloAck`]) are always JSON, since
+//! they're the one thing every version of kstrk must be able to read
+//! regardless of what richer format it negotiates afterwards. The
+//! `Request`/`Response` body is then framed in whichever [`WireFormat`] both
+//! sides advertised, preferring Cap'n Proto (see `schema/ipc.capnp` and
+//! `capnp_codec`) when both peers were built with the `capnp-ipc` feature:
+//! Cap'n Proto's numbered-field struct layout means a future field added to
+//! `StatusInfo`/`StatsInfo`/etc. is just ignored by an older reader, instead
+//! of the reader failing outright the way an unexpected/missing field can
+//! with a plain `serde_json`-tagged enum. A peer that never advertises
+//! `CapnProto` (including every build of kstrk before this feature existed)
+//! is always met with `Json`, so upgrading never breaks interop.
 
+use crate::config::Config;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
 use thiserror::Error;
 
+#[cfg(feature = "capnp-ipc")]
+mod capnp_codec;
+
+/// Identifies this protocol to itself, so a kstrk client never mistakes a
+/// stray socket for a kstrk daemon.
+pub const CHAIN_NAME: &str = "kstrk";
+
+/// Bump whenever `Hello`/`HelloAck`/`Request`/`Response` (or anything
+/// reachable from them) changes shape in a way that isn't forward/backward
+/// compatible for the *JSON* framing. (A `Request`/`Response` field added
+/// under Cap'n Proto doesn't need a bump — see the module docs above.)
+pub const PROTOCOL_VERSION: u16 = 2;
+
 #[derive(Error, Debug)]
 pub enum IpcError {
     #[error("Daemon not running")]
@@ -13,6 +53,78 @@ pub enum IpcError {
 
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
+
+    #[error("protocol mismatch talking to daemon: {0}")]
+    ProtocolMismatch(String),
+
+    #[error("Cap'n Proto error: {0}")]
+    CapnProto(String),
+}
+
+/// A wire format a peer can encode/decode the `Request`/`Response` body in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    Json,
+    CapnProto,
+}
+
+/// The formats this build supports, most preferred first. Cap'n Proto is
+/// only ever advertised by a binary built with the `capnp-ipc` feature (see
+/// `build.rs`), so a daemon/CLI pair where either side lacks it always
+/// negotiates down to `Json`.
+pub(super) fn supported_formats() -> Vec<WireFormat> {
+    if cfg!(feature = "capnp-ipc") {
+        vec![WireFormat::CapnProto, WireFormat::Json]
+    } else {
+        vec![WireFormat::Json]
+    }
+}
+
+/// Pick the best format both sides speak: the first of `ours` (preference
+/// order) that also appears in `theirs`. Falls back to `Json` if the two
+/// lists share nothing, which can only happen if `theirs` is empty — every
+/// build, including this one, always advertises at least `Json`.
+pub(super) fn negotiate(ours: &[WireFormat], theirs: &[WireFormat]) -> WireFormat {
+    ours.iter()
+        .find(|format| theirs.contains(format))
+        .copied()
+        .unwrap_or(WireFormat::Json)
+}
+
+/// First frame sent by a client on every new connection.
+#[derive(Serialize, Deserialize)]
+pub struct Hello {
+    pub chain_name: String,
+    pub protocol_version: u16,
+    /// Wire formats this peer can speak for the `Request`/`Response` body,
+    /// preferred first. Defaults to JSON-only when absent, which is also
+    /// what a pre-negotiation peer that never sends this field means.
+    #[serde(default = "Hello::json_only")]
+    pub supported_formats: Vec<WireFormat>,
+}
+
+impl Hello {
+    fn current() -> Self {
+        Self {
+            chain_name: CHAIN_NAME.to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            supported_formats: supported_formats(),
+        }
+    }
+
+    fn json_only() -> Vec<WireFormat> {
+        vec![WireFormat::Json]
+    }
+}
+
+/// The daemon's reply to a [`Hello`]: either the handshake succeeded (naming
+/// the [`WireFormat`] negotiated for the `Request`/`Response` body that
+/// follows) and the connection may proceed to a real request, or it's
+/// rejected with a reason.
+#[derive(Serialize, Deserialize)]
+pub enum HelloAck {
+    Ok { format: WireFormat },
+    Error { message: String },
 }
 
 #[derive(Serialize, Deserialize)]
@@ -59,13 +171,160 @@ pub struct HeatmapInfo {
     pub data: Vec<(String, u64)>,
 }
 
+/// The path of the Unix-domain socket the daemon listens on and the client
+/// connects to.
+pub fn socket_path(config: &Config) -> std::path::PathBuf {
+    config.data_dir().join("kstrk.sock")
+}
+
+/// Write one length-prefixed JSON frame: the payload's byte length as
+/// decimal text followed by `\n`, then the payload itself.
+pub(super) fn write_frame<T: Serialize>(mut stream: &UnixStream, value: &T) -> Result<(), IpcError> {
+    let payload = serde_json::to_vec(value)?;
+    writeln!(stream, "{}", payload.len())?;
+    stream.write_all(&payload)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Read one length-prefixed JSON frame written by [`write_frame`].
+pub(super) fn read_frame<T: DeserializeOwned>(
+    reader: &mut BufReader<&UnixStream>,
+) -> Result<T, IpcError> {
+    let mut len_line = String::new();
+    let bytes_read = reader.read_line(&mut len_line)?;
+    if bytes_read == 0 {
+        return Err(IpcError::Io(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "connection closed before sending a frame",
+        )));
+    }
+
+    let len: usize = len_line.trim().parse().map_err(|_| {
+        IpcError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "malformed frame length",
+        ))
+    })?;
+
+    let mut payload = vec![0u8; len];
+    std::io::Read::read_exact(reader, &mut payload)?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+/// Write one length-prefixed frame holding raw, already-encoded bytes (as
+/// opposed to [`write_frame`], which encodes `value` as JSON itself).
+fn write_raw_frame(mut stream: &UnixStream, payload: &[u8]) -> Result<(), IpcError> {
+    writeln!(stream, "{}", payload.len())?;
+    stream.write_all(payload)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Read one length-prefixed frame's raw bytes, without assuming a codec.
+fn read_raw_frame(reader: &mut BufReader<&UnixStream>) -> Result<Vec<u8>, IpcError> {
+    let mut len_line = String::new();
+    let bytes_read = reader.read_line(&mut len_line)?;
+    if bytes_read == 0 {
+        return Err(IpcError::Io(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "connection closed before sending a frame",
+        )));
+    }
+    let len: usize = len_line.trim().parse().map_err(|_| {
+        IpcError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "malformed frame length",
+        ))
+    })?;
+    let mut payload = vec![0u8; len];
+    std::io::Read::read_exact(reader, &mut payload)?;
+    Ok(payload)
+}
+
+/// Write a [`Request`] body frame in the negotiated `format`.
+pub(super) fn write_request(
+    stream: &UnixStream,
+    format: WireFormat,
+    request: &Request,
+) -> Result<(), IpcError> {
+    match format {
+        WireFormat::Json => write_frame(stream, request),
+        #[cfg(feature = "capnp-ipc")]
+        WireFormat::CapnProto => write_raw_frame(stream, &capnp_codec::encode_request(request)?),
+        #[cfg(not(feature = "capnp-ipc"))]
+        WireFormat::CapnProto => Err(IpcError::CapnProto(
+            "this build was not compiled with capnp-ipc support".to_string(),
+        )),
+    }
+}
+
+/// Read a [`Request`] body frame written in the negotiated `format`.
+pub(super) fn read_request(
+    reader: &mut BufReader<&UnixStream>,
+    format: WireFormat,
+) -> Result<Request, IpcError> {
+    match format {
+        WireFormat::Json => read_frame(reader),
+        #[cfg(feature = "capnp-ipc")]
+        WireFormat::CapnProto => capnp_codec::decode_request(&read_raw_frame(reader)?),
+        #[cfg(not(feature = "capnp-ipc"))]
+        WireFormat::CapnProto => Err(IpcError::CapnProto(
+            "this build was not compiled with capnp-ipc support".to_string(),
+        )),
+    }
+}
+
+/// Write a [`Response`] body frame in the negotiated `format`.
+pub(super) fn write_response(
+    stream: &UnixStream,
+    format: WireFormat,
+    response: &Response,
+) -> Result<(), IpcError> {
+    match format {
+        WireFormat::Json => write_frame(stream, response),
+        #[cfg(feature = "capnp-ipc")]
+        WireFormat::CapnProto => write_raw_frame(stream, &capnp_codec::encode_response(response)?),
+        #[cfg(not(feature = "capnp-ipc"))]
+        WireFormat::CapnProto => Err(IpcError::CapnProto(
+            "this build was not compiled with capnp-ipc support".to_string(),
+        )),
+    }
+}
+
+/// Read a [`Response`] body frame written in the negotiated `format`.
+pub(super) fn read_response(
+    reader: &mut BufReader<&UnixStream>,
+    format: WireFormat,
+) -> Result<Response, IpcError> {
+    match format {
+        WireFormat::Json => read_frame(reader),
+        #[cfg(feature = "capnp-ipc")]
+        WireFormat::CapnProto => capnp_codec::decode_response(&read_raw_frame(reader)?),
+        #[cfg(not(feature = "capnp-ipc"))]
+        WireFormat::CapnProto => Err(IpcError::CapnProto(
+            "this build was not compiled with capnp-ipc support".to_string(),
+        )),
+    }
+}
+
 pub struct Client;
 
 impl Client {
-    pub fn send(_request: Request) -> Result<Response, IpcError> {
-        // TODO: Implement Unix socket communication
-        // For now, return NotRunning
-        Err(IpcError::NotRunning)
+    /// Connect to the daemon's socket, perform the version handshake, and
+    /// exchange one request/response.
+    pub fn send(config: &Config, request: Request) -> Result<Response, IpcError> {
+        let stream = UnixStream::connect(socket_path(config)).map_err(|_| IpcError::NotRunning)?;
+        let mut reader = BufReader::new(&stream);
+
+        write_frame(&stream, &Hello::current())?;
+        let format = match read_frame::<HelloAck>(&mut reader)? {
+            HelloAck::Ok { format } => format,
+            HelloAck::Error { message } => return Err(IpcError::ProtocolMismatch(message)),
+        };
+
+        write_request(&stream, format, &request)?;
+        read_response(&mut reader, format)
     }
 
     pub fn is_running() -> bool {
@@ -0,0 +1,150 @@
+//! Converts between the generated Cap'n Proto structs (compiled by
+//! `build.rs` from `schema/ipc.capnp`) and the `Request`/`Response` enums in
+//! [`super`]. Only compiled with the `capnp-ipc` feature; see the module
+//! docs on [`super`] for why this exists alongside the JSON framing rather
+//! than instead of it.
+
+include!(concat!(env!("OUT_DIR"), "/ipc_capnp.rs"));
+
+use super::{HeatmapInfo, IpcError, Request, Response, StatsInfo, StatusInfo};
+
+impl From<capnp::Error> for IpcError {
+    fn from(err: capnp::Error) -> Self {
+        IpcError::CapnProto(err.to_string())
+    }
+}
+
+impl From<capnp::NotInSchema> for IpcError {
+    fn from(err: capnp::NotInSchema) -> Self {
+        IpcError::CapnProto(format!("unknown union tag: {err}"))
+    }
+}
+
+pub(super) fn encode_request(request: &Request) -> Result<Vec<u8>, IpcError> {
+    let mut message = capnp::message::Builder::new_default();
+    let mut builder = message.init_root::<request::Builder>();
+    match request {
+        Request::Status => builder.set_status(()),
+        Request::Stop => builder.set_stop(()),
+        Request::GetStats { range } => builder.init_get_stats().set_range(range),
+        Request::GetHeatmap { range } => builder.init_get_heatmap().set_range(range),
+        Request::GetMilestones => builder.set_get_milestones(()),
+        Request::Ping => builder.set_ping(()),
+    }
+    let mut bytes = Vec::new();
+    capnp::serialize::write_message(&mut bytes, &message)?;
+    Ok(bytes)
+}
+
+pub(super) fn decode_request(bytes: &[u8]) -> Result<Request, IpcError> {
+    let reader = capnp::serialize::read_message(bytes, capnp::message::ReaderOptions::new())?;
+    let root = reader.get_root::<request::Reader>()?;
+    Ok(match root.which()? {
+        request::Status(()) => Request::Status,
+        request::Stop(()) => Request::Stop,
+        request::GetStats(params) => Request::GetStats {
+            range: params?.get_range()?.to_string()?,
+        },
+        request::GetHeatmap(params) => Request::GetHeatmap {
+            range: params?.get_range()?.to_string()?,
+        },
+        request::GetMilestones(()) => Request::GetMilestones,
+        request::Ping(()) => Request::Ping,
+    })
+}
+
+pub(super) fn encode_response(response: &Response) -> Result<Vec<u8>, IpcError> {
+    let mut message = capnp::message::Builder::new_default();
+    let mut builder = message.init_root::<response::Builder>();
+    match response {
+        Response::Status(info) => {
+            let mut out = builder.init_status();
+            out.set_pid(info.pid);
+            out.set_uptime_secs(info.uptime_secs);
+            out.set_apm(info.apm);
+            out.set_today_count(info.today_count);
+            out.set_total_count(info.total_count);
+            out.set_streak_days(info.streak_days);
+        }
+        Response::Stats(info) => {
+            let mut out = builder.init_stats();
+            out.set_total_keystrokes(info.total_keystrokes);
+            let mut rows = out.init_by_process(info.by_process.len() as u32);
+            for (i, (process, count)) in info.by_process.iter().enumerate() {
+                let mut row = rows.reborrow().get(i as u32);
+                row.set_process(process);
+                row.set_count(*count);
+            }
+        }
+        Response::Heatmap(info) => {
+            let mut out = builder.init_heatmap();
+            let mut rows = out.init_data(info.data.len() as u32);
+            for (i, (key, count)) in info.data.iter().enumerate() {
+                let mut row = rows.reborrow().get(i as u32);
+                row.set_key(key);
+                row.set_count(*count);
+            }
+        }
+        Response::Milestones(names) => {
+            let mut out = builder.init_milestones(names.len() as u32);
+            for (i, name) in names.iter().enumerate() {
+                out.set(i as u32, name);
+            }
+        }
+        Response::Pong => builder.set_pong(()),
+        Response::Ok => builder.set_ok(()),
+        Response::Error { message } => builder.init_error().set_message(message),
+    }
+    let mut bytes = Vec::new();
+    capnp::serialize::write_message(&mut bytes, &message)?;
+    Ok(bytes)
+}
+
+pub(super) fn decode_response(bytes: &[u8]) -> Result<Response, IpcError> {
+    let reader = capnp::serialize::read_message(bytes, capnp::message::ReaderOptions::new())?;
+    let root = reader.get_root::<response::Reader>()?;
+    Ok(match root.which()? {
+        response::Status(info) => {
+            let info = info?;
+            Response::Status(StatusInfo {
+                pid: info.get_pid(),
+                uptime_secs: info.get_uptime_secs(),
+                apm: info.get_apm(),
+                today_count: info.get_today_count(),
+                total_count: info.get_total_count(),
+                streak_days: info.get_streak_days(),
+            })
+        }
+        response::Stats(info) => {
+            let info = info?;
+            let by_process = info
+                .get_by_process()?
+                .iter()
+                .map(|row| Ok((row.get_process()?.to_string()?, row.get_count())))
+                .collect::<Result<Vec<_>, IpcError>>()?;
+            Response::Stats(StatsInfo {
+                total_keystrokes: info.get_total_keystrokes(),
+                by_process,
+            })
+        }
+        response::Heatmap(info) => {
+            let data = info?
+                .get_data()?
+                .iter()
+                .map(|row| Ok((row.get_key()?.to_string()?, row.get_count())))
+                .collect::<Result<Vec<_>, IpcError>>()?;
+            Response::Heatmap(HeatmapInfo { data })
+        }
+        response::Milestones(names) => Response::Milestones(
+            names?
+                .iter()
+                .map(|name| Ok(name?.to_string()?))
+                .collect::<Result<Vec<_>, IpcError>>()?,
+        ),
+        response::Pong(()) => Response::Pong,
+        response::Ok(()) => Response::Ok,
+        response::Error(info) => Response::Error {
+            message: info?.get_message()?.to_string()?,
+        },
+    })
+}
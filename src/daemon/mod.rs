@@ -3,19 +3,25 @@
 mod ipc;
 mod lock;
 
-pub use ipc::{Client, Request, Response, StatusInfo};
+pub use ipc::{Client, HeatmapInfo, Request, Response, StatsInfo, StatusInfo};
 pub use lock::{InstanceLock, LockError};
 
-use crate::capture::{start_capture, KeyEvent};
+use crate::capture::{chord_for, get_active_window, start_capture, KeyEvent, KeyType};
+use crate::clock::SystemClocks;
 use crate::config::Config;
 use crate::stats::LiveStats;
-use crate::storage::SqliteStorage;
-use std::path::PathBuf;
+use crate::storage::{SessionId, SqliteStorage};
+use std::io::BufReader;
+use std::os::unix::net::{UnixListener, UnixStream};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+/// How often the daemon persists the live session state, so a crash loses
+/// at most this much aggregation work instead of the whole session.
+const SESSION_STATE_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(Error, Debug)]
 pub enum DaemonError {
     #[error("Failed to daemonize: {0}")]
@@ -41,6 +47,11 @@ pub struct Daemon {
     storage: SqliteStorage,
     stats: LiveStats,
     running: Arc<AtomicBool>,
+    prev_key: Option<KeyType>,
+    session_id: SessionId,
+    last_session_save: Instant,
+    last_snapshot: Instant,
+    ipc_listener: UnixListener,
 }
 
 impl Daemon {
@@ -49,19 +60,65 @@ impl Daemon {
         std::fs::create_dir_all(&data_dir)?;
 
         let db_path = data_dir.join("kstrk.db");
-        let storage = SqliteStorage::new(&db_path)
-            .map_err(|e| DaemonError::Capture(e.to_string()))?;
+        let storage = if config.storage.encryption.enabled {
+            let passphrase = std::env::var("KSTRK_PASSPHRASE").map_err(|_| {
+                DaemonError::Capture(
+                    "encryption is enabled but KSTRK_PASSPHRASE is not set".to_string(),
+                )
+            })?;
+            SqliteStorage::new_with_passphrase(&db_path, &passphrase)
+                .map_err(|e| DaemonError::Capture(e.to_string()))?
+        } else {
+            SqliteStorage::new(&db_path).map_err(|e| DaemonError::Capture(e.to_string()))?
+        };
 
-        let stats = LiveStats::new(config.stats.apm_window_secs);
+        let mut stats = LiveStats::new(config.stats.apm_window_secs, Arc::new(SystemClocks));
+
+        // If the daemon didn't shut down cleanly last time, pick up the
+        // dangling session instead of losing its in-flight counters.
+        let session_id = match storage.resume_latest_session() {
+            Ok(Some((session_id, state))) => {
+                stats.restore(state);
+                session_id
+            }
+            Ok(None) => storage
+                .start_session()
+                .map_err(|e| DaemonError::Capture(e.to_string()))?,
+            Err(e) => {
+                eprintln!("Failed to resume previous session, starting fresh: {e}");
+                storage
+                    .start_session()
+                    .map_err(|e| DaemonError::Capture(e.to_string()))?
+            }
+        };
+
+        let ipc_listener = Self::bind_ipc_listener(&config)?;
 
         Ok(Self {
             config,
             storage,
             stats,
             running: Arc::new(AtomicBool::new(true)),
+            prev_key: None,
+            session_id,
+            last_session_save: Instant::now(),
+            last_snapshot: Instant::now(),
+            ipc_listener,
         })
     }
 
+    /// Bind the IPC socket, clearing away a stale socket file left behind by
+    /// a previous daemon that didn't shut down cleanly.
+    fn bind_ipc_listener(config: &Config) -> Result<UnixListener, DaemonError> {
+        let socket_path = ipc::socket_path(config);
+        if socket_path.exists() {
+            std::fs::remove_file(&socket_path)?;
+        }
+        let listener = UnixListener::bind(&socket_path)?;
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    }
+
     pub fn start(foreground: bool, config: Config) -> Result<(), DaemonError> {
         // Acquire instance lock FIRST
         let data_dir = config.data_dir();
@@ -91,8 +148,9 @@ impl Daemon {
         .map_err(|e| DaemonError::Capture(e.to_string()))?;
 
         // Start capture in separate thread
+        let layout = daemon.config.keyboard.layout.clone();
         let (tx, rx) = mpsc::channel::<KeyEvent>();
-        let capture_thread = std::thread::spawn(move || start_capture(tx));
+        let capture_thread = std::thread::spawn(move || start_capture(tx, layout));
 
         // Main loop
         while daemon.running.load(Ordering::SeqCst) {
@@ -101,27 +159,222 @@ impl Daemon {
                 daemon.process_event(event);
             }
 
+            daemon.handle_ipc_connections();
+            daemon.maybe_save_session_state();
+            daemon.maybe_snapshot();
+
             std::thread::sleep(Duration::from_millis(10));
         }
 
+        daemon.shutdown();
         println!("Daemon stopped.");
         Ok(())
     }
 
+    /// Persist the live aggregation buffer if it's been long enough since
+    /// the last snapshot, so a crash loses at most `SESSION_STATE_SAVE_INTERVAL`
+    /// worth of counting.
+    fn maybe_save_session_state(&mut self) {
+        if self.last_session_save.elapsed() < SESSION_STATE_SAVE_INTERVAL {
+            return;
+        }
+        if let Err(e) = self
+            .storage
+            .save_session_state(self.session_id, &self.stats.snapshot())
+        {
+            eprintln!("Failed to save session state: {e}");
+        }
+        self.last_session_save = Instant::now();
+    }
+
+    /// Write a rotated, timestamped `.db` snapshot if snapshots are enabled
+    /// and it's been long enough since the last one, giving users a
+    /// portable export and a recovery path independent of the live
+    /// database file.
+    fn maybe_snapshot(&mut self) {
+        let snapshots = &self.config.storage.snapshots;
+        if !snapshots.enabled {
+            return;
+        }
+        if self.last_snapshot.elapsed() < Duration::from_secs(snapshots.interval_secs) {
+            return;
+        }
+
+        let dir = self.config.data_dir().join("snapshots");
+        let filename = format!("kstrk-{}.db", chrono::Utc::now().format("%Y%m%dT%H%M%SZ"));
+        if let Err(e) = self.storage.snapshot_to(&dir.join(filename)) {
+            eprintln!("Failed to write snapshot: {e}");
+        } else if let Err(e) = crate::storage::rotate_snapshots(&dir, snapshots.retain) {
+            eprintln!("Failed to rotate old snapshots: {e}");
+        }
+        self.last_snapshot = Instant::now();
+    }
+
+    /// Flush a final snapshot, mark the session as cleanly ended, and remove
+    /// the IPC socket file so a stale one doesn't greet the next daemon.
+    fn shutdown(&mut self) {
+        if let Err(e) = self
+            .storage
+            .save_session_state(self.session_id, &self.stats.snapshot())
+        {
+            eprintln!("Failed to save session state: {e}");
+        }
+        if let Err(e) = self
+            .storage
+            .end_session(self.session_id, self.stats.total())
+        {
+            eprintln!("Failed to end session: {e}");
+        }
+        let _ = std::fs::remove_file(ipc::socket_path(&self.config));
+    }
+
+    /// Drain every IPC connection currently waiting on the (non-blocking)
+    /// listener. Each connection is short-lived: handshake, one request, one
+    /// response, close.
+    fn handle_ipc_connections(&mut self) {
+        loop {
+            match self.ipc_listener.accept() {
+                Ok((stream, _addr)) => {
+                    if let Err(e) = self.handle_ipc_connection(stream) {
+                        eprintln!("IPC connection error: {e}");
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("Failed to accept IPC connection: {e}");
+                    break;
+                }
+            }
+        }
+    }
+
+    fn handle_ipc_connection(&mut self, stream: UnixStream) -> Result<(), ipc::IpcError> {
+        stream.set_nonblocking(false)?;
+        let mut reader = BufReader::new(&stream);
+
+        let hello: ipc::Hello = ipc::read_frame(&mut reader)?;
+        if hello.chain_name != ipc::CHAIN_NAME || hello.protocol_version != ipc::PROTOCOL_VERSION {
+            let message = format!(
+                "daemon speaks {} v{}, client spoke {} v{}",
+                ipc::CHAIN_NAME,
+                ipc::PROTOCOL_VERSION,
+                hello.chain_name,
+                hello.protocol_version
+            );
+            ipc::write_frame(&stream, &ipc::HelloAck::Error { message })?;
+            return Ok(());
+        }
+        let format = ipc::negotiate(&ipc::supported_formats(), &hello.supported_formats);
+        ipc::write_frame(&stream, &ipc::HelloAck::Ok { format })?;
+
+        let request = ipc::read_request(&mut reader, format)?;
+        let response = self.handle_request(request);
+        ipc::write_response(&stream, format, &response)?;
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        match request {
+            Request::Ping => Response::Pong,
+            Request::Stop => {
+                self.running.store(false, Ordering::SeqCst);
+                Response::Ok
+            }
+            Request::Status => match self.storage.get_total_keystrokes() {
+                Ok(total_count) => Response::Status(StatusInfo {
+                    pid: std::process::id(),
+                    uptime_secs: self.stats.session_duration().as_secs(),
+                    apm: self.stats.apm(),
+                    // No day-bucketed aggregate exists yet, so "today" is
+                    // approximated by this process's session-so-far total.
+                    today_count: self.stats.total(),
+                    total_count,
+                    streak_days: self.stats.streak(),
+                }),
+                Err(e) => Response::Error {
+                    message: e.to_string(),
+                },
+            },
+            Request::GetStats { range: _ } => {
+                match (
+                    self.storage.get_total_keystrokes(),
+                    self.storage.get_keystrokes_by_process(),
+                ) {
+                    (Ok(total_keystrokes), Ok(by_process)) => Response::Stats(StatsInfo {
+                        total_keystrokes,
+                        by_process,
+                    }),
+                    (Err(e), _) | (_, Err(e)) => Response::Error {
+                        message: e.to_string(),
+                    },
+                }
+            }
+            Request::GetHeatmap { range: _ } => match self.storage.get_heatmap_data() {
+                Ok(data) => Response::Heatmap(HeatmapInfo { data }),
+                Err(e) => Response::Error {
+                    message: e.to_string(),
+                },
+            },
+            Request::GetMilestones => Response::Milestones(
+                self.stats
+                    .milestones()
+                    .iter()
+                    .filter(|m| m.reached_at.is_some())
+                    .map(|m| m.name.to_string())
+                    .collect(),
+            ),
+        }
+    }
+
     fn process_event(&mut self, event: KeyEvent) {
-        // Update live stats
-        if let Some(milestone) = self.stats.record() {
-            println!("{} Milestone reached: {}", milestone.emoji, milestone.name);
+        let window = get_active_window();
+        let capture = self.config.effective_capture(window.as_ref());
+
+        let process_name = window.as_ref().map(|w| w.process_name.as_str()).unwrap_or("Unknown");
+        if capture.ignored_processes.iter().any(|ignored| ignored == process_name) {
+            return;
         }
 
-        // Record to storage (with default window for now)
-        let _ = self.storage.record_keystroke("Unknown", "Unknown", 1);
+        // Record to storage, redacting the process/window identifiers when
+        // `no_text` is in effect (selfspy's `--no-text`: counts only).
+        let (process_name, window_title) = if capture.no_text {
+            ("Redacted", "Redacted")
+        } else {
+            (
+                process_name,
+                window.as_ref().map(|w| w.window_title.as_str()).unwrap_or("Unknown"),
+            )
+        };
+        let _ = self.storage.record_keystroke(process_name, window_title, 1);
+
+        // Update live stats, tagged with which key and application this
+        // event belongs to so `top_keys`/`top_apps`/`apm_for` stay accurate.
+        let stats_event = crate::stats::KeyEvent {
+            key: Some(event.key_type.name()),
+            app: Some(process_name.to_string()),
+        };
+        if let Some(milestone) = self.stats.record_event(stats_event) {
+            println!("{} Milestone reached: {}", milestone.emoji, milestone.name);
+        }
 
         // Record hourly stat for heatmap
         let hour_bucket = event.timestamp.timestamp() / 3600;
         let _ = self
             .storage
             .record_hourly_stat(hour_bucket, &event.key_type.name());
+
+        // Record the key-to-key transition for the bigram digraph export
+        if let Some(prev) = &self.prev_key {
+            let _ = self
+                .storage
+                .record_bigram(hour_bucket, &prev.name(), &event.key_type.name());
+        }
+        self.prev_key = Some(event.key_type.clone());
+
+        // Record modifier+key shortcuts for the "most-used hotkeys" report
+        if let Some(chord) = chord_for(&event) {
+            let _ = self.storage.record_shortcut(&chord.combo(), process_name);
+        }
     }
 
     pub fn is_running() -> bool {
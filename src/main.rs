@@ -6,7 +6,7 @@ use clap::Parser;
 use kstrk::{
     cli::{Cli, Commands, ConfigAction, QueryAction},
     config::Config,
-    daemon::{Client, Daemon},
+    daemon::{Client, Daemon, Request, Response},
     query::QueryEngine,
     storage::SqliteStorage,
     viz,
@@ -24,6 +24,18 @@ fn main() {
     }
 }
 
+/// Translate a `today|week|month|all` range string into an hour-bucket cutoff
+/// (hours since the Unix epoch), matching the `hour_bucket` columns in storage.
+fn range_to_hour_bucket_cutoff(range: &str) -> Option<i64> {
+    let now = chrono::Utc::now().timestamp() / 3600;
+    match range {
+        "today" => Some(now - 24),
+        "week" => Some(now - 24 * 7),
+        "month" => Some(now - 24 * 30),
+        _ => None,
+    }
+}
+
 fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
     match cli.command {
         Commands::Start {
@@ -51,6 +63,9 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
 
             let mut config = Config::load()?;
             config.capture.token_gap_threshold = gap_threshold;
+            if no_text {
+                config.capture.no_text = true;
+            }
 
             println!("✓ Starting keystroke tracking...");
             println!("  Gap threshold: {}ms", gap_threshold);
@@ -67,7 +82,12 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
             if !Client::is_running() {
                 println!("Daemon is not running.");
             } else {
-                println!("TODO: Implement stop via IPC");
+                let config = Config::load()?;
+                match Client::send(&config, Request::Stop)? {
+                    Response::Ok => println!("Daemon stopped."),
+                    Response::Error { message } => eprintln!("Failed to stop daemon: {message}"),
+                    _ => eprintln!("Unexpected response from daemon."),
+                }
             }
         }
 
@@ -76,14 +96,46 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                 println!("✗ Daemon is not running");
                 println!("\nStart with: kstrk start");
             } else {
-                println!("✓ Daemon is running");
-                println!("TODO: Show full status via IPC");
+                let config = Config::load()?;
+                match Client::send(&config, Request::Status)? {
+                    Response::Status(status) => {
+                        println!("✓ Daemon is running (pid {})", status.pid);
+                        println!("  Uptime:  {}s", status.uptime_secs);
+                        println!("  APM:     {:.1}", status.apm);
+                        println!("  Today:   {}", status.today_count);
+                        println!("  Total:   {}", status.total_count);
+                        println!("  Streak:  {} day(s)", status.streak_days);
+                    }
+                    Response::Error { message } => eprintln!("Failed to get status: {message}"),
+                    _ => eprintln!("Unexpected response from daemon."),
+                }
             }
         }
 
         Commands::Watch { interval } => {
-            println!("Live watch mode (refresh every {}ms)", interval);
-            println!("TODO: Implement live watch");
+            if !Client::is_running() {
+                println!("Daemon is not running. Start with: kstrk start");
+                return Ok(());
+            }
+            let config = Config::load()?;
+            println!("Live watch mode (refresh every {}ms, Ctrl+C to stop)", interval);
+            loop {
+                match Client::send(&config, Request::Status) {
+                    Ok(Response::Status(status)) => {
+                        println!(
+                            "apm={:.1} today={} total={} streak={}d",
+                            status.apm, status.today_count, status.total_count, status.streak_days
+                        );
+                    }
+                    Ok(Response::Error { message }) => eprintln!("Error: {message}"),
+                    Ok(_) => eprintln!("Unexpected response from daemon."),
+                    Err(e) => {
+                        eprintln!("Lost connection to daemon: {e}");
+                        break;
+                    }
+                }
+                std::thread::sleep(std::time::Duration::from_millis(interval));
+            }
         }
 
         Commands::Query { action } => {
@@ -129,6 +181,32 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                     println!("Key frequency distribution:");
                     println!("TODO: Implement key frequency query");
                 }
+                QueryAction::Shortcuts { process, limit } => {
+                    println!("Most-used shortcuts:\n");
+                    let results = engine.top_shortcuts(process.as_deref(), limit)?;
+                    for (combo, count) in results {
+                        println!("  {:20} {:>10}", combo, count);
+                    }
+                }
+                QueryAction::Run { expr } => {
+                    use kstrk::query::QueryResult;
+
+                    match engine.run(&expr)? {
+                        QueryResult::ByProcess(rows) => {
+                            for (process, count) in rows {
+                                println!("  {:30} {:>10}", process, count);
+                            }
+                        }
+                        QueryResult::ByWindow(rows) => {
+                            for (process, title, count) in rows {
+                                println!("  {} / {} : {}", process, title, count);
+                            }
+                        }
+                        QueryResult::Total(count) => {
+                            println!("{}", count);
+                        }
+                    }
+                }
             }
         }
 
@@ -214,9 +292,33 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        Commands::Export { output, format } => {
-            println!("Exporting to {:?} as {}", output, format);
-            println!("TODO: Implement export");
+        Commands::Export {
+            output,
+            format,
+            range,
+            min_weight,
+            undirected,
+        } => {
+            if format == "dot" {
+                let config = Config::load()?;
+                let db_path = config.data_dir().join("kstrk.db");
+
+                if !db_path.exists() {
+                    eprintln!("No data found. Start tracking first with: kstrk start");
+                    return Ok(());
+                }
+
+                let storage = SqliteStorage::new(&db_path)?;
+                let since_hour_bucket = range_to_hour_bucket_cutoff(&range);
+                let bigrams = storage.get_bigrams(since_hour_bucket)?;
+                let dot = viz::render_dot_graph(&bigrams, undirected, min_weight);
+
+                std::fs::write(&output, dot)?;
+                println!("Exported keystroke transition graph to {:?}", output);
+            } else {
+                println!("Exporting to {:?} as {}", output, format);
+                println!("TODO: Implement export");
+            }
         }
 
         Commands::Config { action } => match action {
@@ -232,10 +334,21 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                     std::process::Command::new(editor).arg(&path).status()?;
                 }
             }
-            ConfigAction::Show => {
+            ConfigAction::Show { profile } => {
                 let config = Config::load()?;
-                let toml = toml::to_string_pretty(&config)?;
-                println!("{}", toml);
+                if let Some(name) = profile {
+                    let Some(selected) = config.profile.get(&name) else {
+                        eprintln!("Unknown profile '{}'", name);
+                        return Ok(());
+                    };
+                    let mut effective = config.clone();
+                    effective.capture = effective.effective_capture_for_profile(selected);
+                    let toml = toml::to_string_pretty(&effective)?;
+                    println!("{}", toml);
+                } else {
+                    let toml = toml::to_string_pretty(&config)?;
+                    println!("{}", toml);
+                }
             }
             ConfigAction::Reset => {
                 Config::default().save()?;
@@ -246,6 +359,53 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                     println!("{}", path.display());
                 }
             }
+            ConfigAction::SetLayout { layout } => {
+                use kstrk::capture::Layout;
+
+                let layout = match layout.to_lowercase().as_str() {
+                    "qwerty" => Layout::Qwerty,
+                    "dvorak" => Layout::Dvorak,
+                    "colemak" => Layout::Colemak,
+                    other => {
+                        eprintln!(
+                            "Unknown layout '{}': expected qwerty, dvorak, or colemak",
+                            other
+                        );
+                        return Ok(());
+                    }
+                };
+
+                let mut config = Config::load()?;
+                config.keyboard.layout = layout;
+                config.save()?;
+                println!("✓ Keyboard layout set to {:?}", config.keyboard.layout);
+            }
+            ConfigAction::Profiles => {
+                let config = Config::load()?;
+                if config.profile.is_empty() {
+                    println!("No profiles defined.");
+                    return Ok(());
+                }
+                for (name, profile) in &config.profile {
+                    let is_default = config.default_profile.as_deref() == Some(name.as_str());
+                    println!("{}{}", name, if is_default { " (default)" } else { "" });
+                    if !profile.match_process.is_empty() {
+                        println!("  match_process: {:?}", profile.match_process);
+                    }
+                    if !profile.match_title.is_empty() {
+                        println!("  match_title: {:?}", profile.match_title);
+                    }
+                    if let Some(threshold) = profile.token_gap_threshold {
+                        println!("  token_gap_threshold: {}", threshold);
+                    }
+                    if let Some(no_text) = profile.no_text {
+                        println!("  no_text: {}", no_text);
+                    }
+                    if !profile.ignored_processes.is_empty() {
+                        println!("  ignored_processes: {:?}", profile.ignored_processes);
+                    }
+                }
+            }
         },
     }
 
@@ -0,0 +1,260 @@
+//! Lexer for the kstrk query language.
+//!
+//! Scans a query string such as
+//! `SELECT keys BY process WHERE process ~ "Code" AND date >= "2024-01-01" LIMIT 10`,
+//! or a bare filter expression such as
+//! `process = "Code" and count > 100 and hour between 9 and 17`,
+//! into a flat token stream. Each token carries the byte offset it started at so
+//! parse errors can point at a column in the original input.
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Select,
+    By,
+    Where,
+    And,
+    Or,
+    Between,
+    Order,
+    Limit,
+    Desc,
+    Asc,
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Match,
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+    LParen,
+    RParen,
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub offset: usize,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum LexError {
+    #[error("unexpected character '{ch}' at column {offset}")]
+    UnexpectedChar { ch: char, offset: usize },
+
+    #[error("unterminated string literal starting at column {offset}")]
+    UnterminatedString { offset: usize },
+}
+
+pub struct Lexer<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            chars: input.char_indices().peekable(),
+        }
+    }
+
+    /// Scan the entire input into a token stream, ending in `TokenKind::Eof`.
+    pub fn tokenize(mut self) -> Result<Vec<Token>, LexError> {
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek().copied() {
+                None => {
+                    tokens.push(Token {
+                        kind: TokenKind::Eof,
+                        offset: self.input.len(),
+                    });
+                    break;
+                }
+                Some((offset, ch)) => {
+                    let token = if ch == '"' {
+                        self.read_string(offset)?
+                    } else if ch.is_ascii_digit() {
+                        self.read_integer(offset)
+                    } else if ch.is_alphabetic() || ch == '_' {
+                        self.read_word(offset)
+                    } else {
+                        self.read_operator(offset)?
+                    };
+                    tokens.push(token);
+                }
+            }
+        }
+        Ok(tokens)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.chars.peek(), Some((_, ch)) if ch.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn read_string(&mut self, offset: usize) -> Result<Token, LexError> {
+        self.chars.next(); // consume opening quote
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(Token { kind: TokenKind::Str(value), offset }),
+                Some((_, ch)) => value.push(ch),
+                None => return Err(LexError::UnterminatedString { offset }),
+            }
+        }
+    }
+
+    fn read_integer(&mut self, offset: usize) -> Token {
+        let mut value = String::new();
+        while matches!(self.chars.peek(), Some((_, ch)) if ch.is_ascii_digit()) {
+            value.push(self.chars.next().unwrap().1);
+        }
+        Token {
+            kind: TokenKind::Int(value.parse().unwrap_or(0)),
+            offset,
+        }
+    }
+
+    fn read_word(&mut self, offset: usize) -> Token {
+        let mut value = String::new();
+        while matches!(self.chars.peek(), Some((_, ch)) if ch.is_alphanumeric() || *ch == '_') {
+            value.push(self.chars.next().unwrap().1);
+        }
+        let kind = match value.to_uppercase().as_str() {
+            "SELECT" => TokenKind::Select,
+            "BY" => TokenKind::By,
+            "WHERE" => TokenKind::Where,
+            "AND" => TokenKind::And,
+            "OR" => TokenKind::Or,
+            "BETWEEN" => TokenKind::Between,
+            "ORDER" => TokenKind::Order,
+            "LIMIT" => TokenKind::Limit,
+            "DESC" => TokenKind::Desc,
+            "ASC" => TokenKind::Asc,
+            _ => TokenKind::Ident(value),
+        };
+        Token { kind, offset }
+    }
+
+    fn read_operator(&mut self, offset: usize) -> Result<Token, LexError> {
+        let (_, ch) = self.chars.next().unwrap();
+        let kind = match ch {
+            '~' => TokenKind::Match,
+            '=' => TokenKind::Eq,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '!' => {
+                if matches!(self.chars.peek(), Some((_, '='))) {
+                    self.chars.next();
+                    TokenKind::Ne
+                } else {
+                    return Err(LexError::UnexpectedChar { ch, offset });
+                }
+            }
+            '>' => {
+                if matches!(self.chars.peek(), Some((_, '='))) {
+                    self.chars.next();
+                    TokenKind::Ge
+                } else {
+                    TokenKind::Gt
+                }
+            }
+            '<' => {
+                if matches!(self.chars.peek(), Some((_, '='))) {
+                    self.chars.next();
+                    TokenKind::Le
+                } else {
+                    TokenKind::Lt
+                }
+            }
+            other => return Err(LexError::UnexpectedChar { ch: other, offset }),
+        };
+        Ok(Token { kind, offset })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenizes_simple_select() {
+        let tokens = Lexer::new("SELECT keys BY process LIMIT 10").tokenize().unwrap();
+        assert_eq!(
+            tokens.iter().map(|t| t.kind.clone()).collect::<Vec<_>>(),
+            vec![
+                TokenKind::Select,
+                TokenKind::Ident("keys".into()),
+                TokenKind::By,
+                TokenKind::Ident("process".into()),
+                TokenKind::Limit,
+                TokenKind::Int(10),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizes_filter_expression() {
+        let tokens = Lexer::new(r#"process ~ "Code" AND date >= "2024-01-01""#)
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens.iter().map(|t| t.kind.clone()).collect::<Vec<_>>(),
+            vec![
+                TokenKind::Ident("process".into()),
+                TokenKind::Match,
+                TokenKind::Str("Code".into()),
+                TokenKind::And,
+                TokenKind::Ident("date".into()),
+                TokenKind::Ge,
+                TokenKind::Str("2024-01-01".into()),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_string_reports_offset() {
+        let err = Lexer::new(r#"process ~ "Code"#).tokenize().unwrap_err();
+        assert_eq!(err, LexError::UnterminatedString { offset: 10 });
+    }
+
+    #[test]
+    fn test_tokenizes_bare_filter_expression_with_between_and_or() {
+        let tokens = Lexer::new(r#"process != "Code" or (count > 100 and hour between 9 and 17)"#)
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens.iter().map(|t| t.kind.clone()).collect::<Vec<_>>(),
+            vec![
+                TokenKind::Ident("process".into()),
+                TokenKind::Ne,
+                TokenKind::Str("Code".into()),
+                TokenKind::Or,
+                TokenKind::LParen,
+                TokenKind::Ident("count".into()),
+                TokenKind::Gt,
+                TokenKind::Int(100),
+                TokenKind::And,
+                TokenKind::Ident("hour".into()),
+                TokenKind::Between,
+                TokenKind::Int(9),
+                TokenKind::And,
+                TokenKind::Int(17),
+                TokenKind::RParen,
+                TokenKind::Eof,
+            ]
+        );
+    }
+}
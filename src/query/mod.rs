@@ -1,6 +1,40 @@
 //! Query module for data analysis (inspired by selfspy's selfstats)
 
+mod lexer;
+mod parser;
+
+pub use parser::{
+    parse, Filter, FilterExpr, FilterValue, GroupBy, Metric, Op, Order, OrderDirection, Query,
+};
+
 use crate::storage::SqliteStorage;
+use rusqlite::types::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum QueryError {
+    #[error("database error: {0}")]
+    Storage(#[from] rusqlite::Error),
+
+    #[error("invalid query: {0}")]
+    Parse(#[from] parser::ParseError),
+
+    #[error("invalid regex: {0}")]
+    Regex(#[from] regex::Error),
+
+    #[error("field '{0}' is not supported in this query context")]
+    UnsupportedField(String),
+}
+
+/// The result of running a parsed [`Query`], shaped by its `group_by`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryResult {
+    ByProcess(Vec<(String, u64)>),
+    ByWindow(Vec<(String, String, u64)>),
+    /// A bare filter expression with no `BY` clause: a single keystroke
+    /// count, optionally restricted by an `hour` predicate.
+    Total(u64),
+}
 
 pub struct QueryEngine<'a> {
     storage: &'a SqliteStorage,
@@ -18,22 +52,333 @@ impl<'a> QueryEngine<'a> {
         Ok(results)
     }
 
-    /// Query keystrokes by window
+    /// Query keystrokes by window, optionally filtering by a window-title and/or
+    /// process-name regex.
     pub fn by_window(
         &self,
-        _title_pattern: Option<&str>,
-        _process_pattern: Option<&str>,
+        title_pattern: Option<&str>,
+        process_pattern: Option<&str>,
         limit: usize,
-    ) -> Result<Vec<(String, String, u64)>, rusqlite::Error> {
-        // TODO: Implement window-based query with regex filtering
-        // For now, return empty
-        Ok(Vec::new())
+    ) -> Result<Vec<(String, String, u64)>, QueryError> {
+        let title_re = title_pattern.map(regex::Regex::new).transpose()?;
+        let process_re = process_pattern.map(regex::Regex::new).transpose()?;
+
+        let mut results: Vec<_> = self
+            .storage
+            .get_keystrokes_by_window()?
+            .into_iter()
+            .filter(|(process, title, _)| {
+                process_re.as_ref().map_or(true, |re| re.is_match(process))
+                    && title_re.as_ref().map_or(true, |re| re.is_match(title))
+            })
+            .collect();
+
+        results.truncate(limit);
+        Ok(results)
     }
 
     /// Get total keystrokes
     pub fn total_keystrokes(&self) -> Result<u64, rusqlite::Error> {
         self.storage.get_total_keystrokes()
     }
+
+    /// Most-used shortcuts, overall or restricted to a single process
+    pub fn top_shortcuts(
+        &self,
+        process: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(String, u64)>, rusqlite::Error> {
+        match process {
+            Some(process) => self.storage.get_top_shortcuts_by_process(process, limit),
+            None => self.storage.get_top_shortcuts(limit),
+        }
+    }
+
+    /// Parse and run a query expression against storage. Accepts either a
+    /// full `SELECT keys BY process WHERE process ~ "Code" LIMIT 10` query,
+    /// or a bare filter expression, which behaves like a `WHERE` clause with
+    /// no `BY` and returns a [`QueryResult::Total`]. A bare expression on
+    /// `hour` alone (compared against hours-since-epoch buckets) is pushed
+    /// into SQL; one on `process`/`count` alone, like
+    /// `process = "Code" and count > 100`, is evaluated per process and
+    /// summed. A bare expression that mixes `hour` with `process`/`count` is
+    /// rejected with [`QueryError::UnsupportedField`].
+    pub fn run(&self, expr: &str) -> Result<QueryResult, QueryError> {
+        let query = parse(expr)?;
+
+        match query.group_by {
+            Some(GroupBy::Window) => {
+                let mut rows = self.storage.get_keystrokes_by_window()?;
+                self.apply_window_filters(&mut rows, query.filter.as_ref())?;
+                apply_window_order(&mut rows, query.order.as_ref())?;
+                rows.truncate(query.limit.unwrap_or(usize::MAX));
+                Ok(QueryResult::ByWindow(rows))
+            }
+            Some(GroupBy::Process) => {
+                let mut rows = self.storage.get_keystrokes_by_process()?;
+                self.apply_process_filters(&mut rows, query.filter.as_ref())?;
+                apply_process_order(&mut rows, query.order.as_ref())?;
+                rows.truncate(query.limit.unwrap_or(usize::MAX));
+                Ok(QueryResult::ByProcess(rows))
+            }
+            Some(GroupBy::Key) | Some(GroupBy::Date) => Err(QueryError::UnsupportedField(
+                "group-by on key/date is not yet backed by storage".to_string(),
+            )),
+            // `hour` lives on a cleartext column and can be pushed into SQL;
+            // `process`/`count` are only available as decrypted per-process
+            // aggregates, so a bare filter referencing either is evaluated in
+            // Rust the same way the `BY process` path does, then summed.
+            None => match &query.filter {
+                Some(filter) if filter_references_only(filter, "hour") => {
+                    let (predicate_sql, params) = lower_to_sql(filter)?;
+                    Ok(QueryResult::Total(
+                        self.storage.count_keys_matching(&predicate_sql, &params)?,
+                    ))
+                }
+                Some(filter) => {
+                    let mut rows = self.storage.get_keystrokes_by_process()?;
+                    self.apply_process_filters(&mut rows, Some(filter))?;
+                    Ok(QueryResult::Total(rows.iter().map(|(_, count)| count).sum()))
+                }
+                None => Ok(QueryResult::Total(self.storage.get_total_keystrokes()?)),
+            },
+        }
+    }
+
+    fn apply_process_filters(
+        &self,
+        rows: &mut Vec<(String, u64)>,
+        filter: Option<&FilterExpr>,
+    ) -> Result<(), QueryError> {
+        let Some(filter) = filter else { return Ok(()) };
+        check_fields(filter, &["process", "count"])?;
+        let mut err = None;
+        rows.retain(|(process, count)| match eval_process_filter(filter, process, *count) {
+            Ok(keep) => keep,
+            Err(e) => {
+                err.get_or_insert(e);
+                false
+            }
+        });
+        err.map_or(Ok(()), Err)
+    }
+
+    fn apply_window_filters(
+        &self,
+        rows: &mut Vec<(String, String, u64)>,
+        filter: Option<&FilterExpr>,
+    ) -> Result<(), QueryError> {
+        let Some(filter) = filter else { return Ok(()) };
+        check_fields(filter, &["process", "window", "count"])?;
+        let mut err = None;
+        rows.retain(
+            |(process, title, count)| match eval_window_filter(filter, process, title, *count) {
+                Ok(keep) => keep,
+                Err(e) => {
+                    err.get_or_insert(e);
+                    false
+                }
+            },
+        );
+        err.map_or(Ok(()), Err)
+    }
+}
+
+/// Evaluate a filter over an aggregated `(process, count)` row. Text fields
+/// are captured encrypted at rest, so `process`/`count` are matched here in
+/// Rust against the already-decrypted aggregate rather than pushed into SQL.
+fn eval_process_filter(expr: &FilterExpr, process: &str, count: u64) -> Result<bool, QueryError> {
+    match expr {
+        FilterExpr::And(a, b) => {
+            Ok(eval_process_filter(a, process, count)? && eval_process_filter(b, process, count)?)
+        }
+        FilterExpr::Or(a, b) => {
+            Ok(eval_process_filter(a, process, count)? || eval_process_filter(b, process, count)?)
+        }
+        FilterExpr::Comparison(filter) => match filter.field.as_str() {
+            "process" => match_str(filter, process),
+            "count" => match_int(filter, count as i64),
+            other => Err(QueryError::UnsupportedField(other.to_string())),
+        },
+    }
+}
+
+/// Evaluate a filter over an aggregated `(process, window, count)` row; see
+/// [`eval_process_filter`] for why this stays in Rust rather than SQL.
+fn eval_window_filter(
+    expr: &FilterExpr,
+    process: &str,
+    title: &str,
+    count: u64,
+) -> Result<bool, QueryError> {
+    match expr {
+        FilterExpr::And(a, b) => Ok(eval_window_filter(a, process, title, count)?
+            && eval_window_filter(b, process, title, count)?),
+        FilterExpr::Or(a, b) => Ok(eval_window_filter(a, process, title, count)?
+            || eval_window_filter(b, process, title, count)?),
+        FilterExpr::Comparison(filter) => match filter.field.as_str() {
+            "process" => match_str(filter, process),
+            "window" => match_str(filter, title),
+            "count" => match_int(filter, count as i64),
+            other => Err(QueryError::UnsupportedField(other.to_string())),
+        },
+    }
+}
+
+/// Apply a parsed `ORDER BY` to a `BY process` result, ascending/descending as
+/// requested. Storage already hands back rows sorted by count descending, but
+/// that's an implementation detail of `get_keystrokes_by_process`, not a
+/// substitute for honoring the query's own `order` — so this always re-sorts
+/// rather than trusting the existing order.
+fn apply_process_order(rows: &mut [(String, u64)], order: Option<&Order>) -> Result<(), QueryError> {
+    let Some(order) = order else { return Ok(()) };
+    match order.field.as_str() {
+        "count" => rows.sort_by_key(|(_, count)| *count),
+        "process" => rows.sort_by(|a, b| a.0.cmp(&b.0)),
+        other => return Err(QueryError::UnsupportedField(other.to_string())),
+    }
+    if order.direction == OrderDirection::Desc {
+        rows.reverse();
+    }
+    Ok(())
+}
+
+/// Apply a parsed `ORDER BY` to a `BY window` result; see
+/// [`apply_process_order`] for why this re-sorts rather than trusting
+/// storage's default order.
+fn apply_window_order(
+    rows: &mut [(String, String, u64)],
+    order: Option<&Order>,
+) -> Result<(), QueryError> {
+    let Some(order) = order else { return Ok(()) };
+    match order.field.as_str() {
+        "count" => rows.sort_by_key(|(_, _, count)| *count),
+        "process" => rows.sort_by(|a, b| a.0.cmp(&b.0)),
+        "window" => rows.sort_by(|a, b| a.1.cmp(&b.1)),
+        other => return Err(QueryError::UnsupportedField(other.to_string())),
+    }
+    if order.direction == OrderDirection::Desc {
+        rows.reverse();
+    }
+    Ok(())
+}
+
+/// Whether every comparison in `expr` filters on `field`, used to decide
+/// whether a bare filter expression can be lowered straight into SQL (only
+/// `hour` can) or must be evaluated in Rust against decrypted aggregates.
+fn filter_references_only(expr: &FilterExpr, field: &str) -> bool {
+    match expr {
+        FilterExpr::And(a, b) | FilterExpr::Or(a, b) => {
+            filter_references_only(a, field) && filter_references_only(b, field)
+        }
+        FilterExpr::Comparison(filter) => filter.field == field,
+    }
+}
+
+/// Fail with [`QueryError::UnsupportedField`] on the first comparison whose
+/// field is not in `allowed`, before any row is evaluated, so an unsupported
+/// field is rejected even when no row would reach that comparison.
+fn check_fields(expr: &FilterExpr, allowed: &[&str]) -> Result<(), QueryError> {
+    match expr {
+        FilterExpr::And(a, b) | FilterExpr::Or(a, b) => {
+            check_fields(a, allowed)?;
+            check_fields(b, allowed)
+        }
+        FilterExpr::Comparison(filter) if allowed.contains(&filter.field.as_str()) => Ok(()),
+        FilterExpr::Comparison(filter) => Err(QueryError::UnsupportedField(filter.field.clone())),
+    }
+}
+
+fn match_str(filter: &Filter, actual: &str) -> Result<bool, QueryError> {
+    match &filter.value {
+        FilterValue::Str(pattern) if filter.op == Op::Match => {
+            Ok(regex::Regex::new(pattern)?.is_match(actual))
+        }
+        FilterValue::Str(expected) => match filter.op {
+            Op::Eq => Ok(actual == expected),
+            Op::Ne => Ok(actual != expected),
+            _ => Err(QueryError::UnsupportedField(format!(
+                "'{}' only supports =, !=, and ~",
+                filter.field
+            ))),
+        },
+        FilterValue::Int(_) => Err(QueryError::UnsupportedField(format!(
+            "'{}' filter expects a string value",
+            filter.field
+        ))),
+    }
+}
+
+fn match_int(filter: &Filter, actual: i64) -> Result<bool, QueryError> {
+    let FilterValue::Int(expected) = filter.value else {
+        return Err(QueryError::UnsupportedField(format!(
+            "'{}' filter expects an integer value",
+            filter.field
+        )));
+    };
+    match filter.op {
+        Op::Eq => Ok(actual == expected),
+        Op::Ne => Ok(actual != expected),
+        Op::Ge => Ok(actual >= expected),
+        Op::Le => Ok(actual <= expected),
+        Op::Gt => Ok(actual > expected),
+        Op::Lt => Ok(actual < expected),
+        Op::Match => Err(QueryError::UnsupportedField(format!(
+            "'{}' does not support ~",
+            filter.field
+        ))),
+    }
+}
+
+/// Lower a [`FilterExpr`] into a SQL `WHERE`-clause fragment plus its bound
+/// parameters, for filters that can be pushed straight onto a cleartext
+/// column (currently just `hour`, which maps to the `ops.hour_bucket`
+/// column). Comparison operators are rendered from the typed [`Op`] enum and
+/// values are always returned as bind parameters, never spliced into the SQL
+/// text, so a filter value can never break out of its `?` placeholder.
+fn lower_to_sql(expr: &FilterExpr) -> Result<(String, Vec<Value>), QueryError> {
+    match expr {
+        FilterExpr::And(a, b) => lower_binary(a, b, "AND"),
+        FilterExpr::Or(a, b) => lower_binary(a, b, "OR"),
+        FilterExpr::Comparison(filter) => {
+            if filter.field != "hour" {
+                return Err(QueryError::UnsupportedField(format!(
+                    "'{}' can only be filtered alongside a BY clause, not in a bare expression",
+                    filter.field
+                )));
+            }
+            let FilterValue::Int(n) = filter.value else {
+                return Err(QueryError::UnsupportedField(
+                    "hour filter expects an integer value".to_string(),
+                ));
+            };
+            let op_sql = match filter.op {
+                Op::Eq => "=",
+                Op::Ne => "!=",
+                Op::Ge => ">=",
+                Op::Le => "<=",
+                Op::Gt => ">",
+                Op::Lt => "<",
+                Op::Match => {
+                    return Err(QueryError::UnsupportedField(
+                        "hour does not support ~".to_string(),
+                    ))
+                }
+            };
+            Ok((format!("hour_bucket {op_sql} ?"), vec![Value::Integer(n)]))
+        }
+    }
+}
+
+fn lower_binary(
+    a: &FilterExpr,
+    b: &FilterExpr,
+    joiner: &str,
+) -> Result<(String, Vec<Value>), QueryError> {
+    let (a_sql, mut params) = lower_to_sql(a)?;
+    let (b_sql, b_params) = lower_to_sql(b)?;
+    params.extend(b_params);
+    Ok((format!("({a_sql} {joiner} {b_sql})"), params))
 }
 
 #[cfg(test)]
@@ -48,4 +393,126 @@ mod tests {
         let total = engine.total_keystrokes().unwrap();
         assert_eq!(total, 0);
     }
+
+    #[test]
+    fn test_by_window_regex_filters() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        storage.record_keystroke("Code", "main.rs", 10).unwrap();
+        storage.record_keystroke("Terminal", "zsh", 5).unwrap();
+
+        let engine = QueryEngine::new(&storage);
+        let results = engine.by_window(None, Some("^Code$"), 20).unwrap();
+        assert_eq!(results, vec![("Code".to_string(), "main.rs".to_string(), 10)]);
+    }
+
+    #[test]
+    fn test_run_select_by_process_with_regex_filter() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        storage.record_keystroke("Code", "main.rs", 10).unwrap();
+        storage.record_keystroke("Terminal", "zsh", 5).unwrap();
+
+        let engine = QueryEngine::new(&storage);
+        let result = engine
+            .run(r#"SELECT keys BY process WHERE process ~ "^Code$""#)
+            .unwrap();
+        assert_eq!(result, QueryResult::ByProcess(vec![("Code".to_string(), 10)]));
+    }
+
+    #[test]
+    fn test_run_rejects_unsupported_field() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        let engine = QueryEngine::new(&storage);
+        let err = engine
+            .run(r#"SELECT keys BY process WHERE date >= "2024-01-01""#)
+            .unwrap_err();
+        assert!(matches!(err, QueryError::UnsupportedField(_)));
+    }
+
+    #[test]
+    fn test_run_bare_filter_expression_with_and_returns_a_total() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        storage.record_keystroke("Code", "main.rs", 10).unwrap();
+        storage.record_keystroke("Terminal", "zsh", 5).unwrap();
+
+        let engine = QueryEngine::new(&storage);
+        let result = engine
+            .run(r#"process = "Code" and count > 5"#)
+            .unwrap();
+        assert_eq!(result, QueryResult::Total(10));
+    }
+
+    #[test]
+    fn test_run_bare_filter_expression_with_or_across_processes() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        storage.record_keystroke("Code", "main.rs", 10).unwrap();
+        storage.record_keystroke("Terminal", "zsh", 5).unwrap();
+
+        let engine = QueryEngine::new(&storage);
+        let result = engine
+            .run(r#"process = "Code" or process = "Terminal""#)
+            .unwrap();
+        assert_eq!(result, QueryResult::Total(15));
+    }
+
+    #[test]
+    fn test_run_hour_filter_is_lowered_to_a_parameterized_sql_predicate() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        storage.record_keystroke("Code", "main.rs", 10).unwrap();
+        let hour = storage.export_ops_since("").unwrap()[0].hour_bucket;
+
+        let engine = QueryEngine::new(&storage);
+        let result = engine
+            .run(&format!("hour between {} and {}", hour, hour))
+            .unwrap();
+        assert_eq!(result, QueryResult::Total(10));
+
+        let result = engine.run(&format!("hour = {}", hour + 1000)).unwrap();
+        assert_eq!(result, QueryResult::Total(0));
+    }
+
+    #[test]
+    fn test_run_applies_parsed_order_by_instead_of_storages_default_order() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        storage.record_keystroke("Code", "main.rs", 10).unwrap();
+        storage.record_keystroke("Terminal", "zsh", 50).unwrap();
+
+        let engine = QueryEngine::new(&storage);
+        // Storage's own default order is count descending, so ASC here only
+        // passes if `run` actually applies the parsed `ORDER BY`.
+        let result = engine
+            .run("SELECT keys BY process ORDER BY count ASC")
+            .unwrap();
+        assert_eq!(
+            result,
+            QueryResult::ByProcess(vec![("Code".to_string(), 10), ("Terminal".to_string(), 50)])
+        );
+
+        let result = engine
+            .run("SELECT keys BY process ORDER BY count DESC")
+            .unwrap();
+        assert_eq!(
+            result,
+            QueryResult::ByProcess(vec![("Terminal".to_string(), 50), ("Code".to_string(), 10)])
+        );
+    }
+
+    #[test]
+    fn test_run_rejects_an_order_by_on_an_unsupported_field() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        let engine = QueryEngine::new(&storage);
+        let err = engine
+            .run("SELECT keys BY process ORDER BY bogus DESC")
+            .unwrap_err();
+        assert!(matches!(err, QueryError::UnsupportedField(_)));
+    }
+
+    #[test]
+    fn test_run_no_filter_returns_the_grand_total() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        storage.record_keystroke("Code", "main.rs", 10).unwrap();
+        storage.record_keystroke("Terminal", "zsh", 5).unwrap();
+
+        let engine = QueryEngine::new(&storage);
+        assert_eq!(engine.run("SELECT keys").unwrap(), QueryResult::Total(15));
+    }
 }
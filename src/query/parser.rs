@@ -0,0 +1,448 @@
+//! Recursive-descent parser for the kstrk query language.
+//!
+//! Turns the token stream produced by [`super::lexer::Lexer`] into a typed
+//! [`Query`] describing the metric to compute, how to group it, the filter to
+//! apply, and the requested ordering/limit. A query is either a full
+//! `SELECT ... BY ... WHERE ... LIMIT ...` statement, or a bare filter
+//! expression such as `process = "Code" and count > 100 and hour between 9
+//! and 17` — in the latter case `group_by` and `order`/`limit` are left
+//! unset and `QueryEngine` treats it as a plain keystroke-count filter.
+
+use super::lexer::{LexError, Lexer, Token, TokenKind};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ParseError {
+    #[error(transparent)]
+    Lex(#[from] LexError),
+
+    #[error("unexpected token at column {offset}: {message}")]
+    Unexpected { offset: usize, message: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    Keys,
+    Freq,
+    Active,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    Process,
+    Window,
+    Key,
+    Date,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Match,
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    Str(String),
+    Int(i64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub field: String,
+    pub op: Op,
+    pub value: FilterValue,
+}
+
+/// A boolean tree of filter comparisons, e.g. `process = "Code" and (count >
+/// 100 or hour between 9 and 17)`. `between` desugars to an `And` of two
+/// `Ge`/`Le` comparisons on the same field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    Comparison(Filter),
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderDirection {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    pub field: String,
+    pub direction: OrderDirection,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub metric: Metric,
+    pub group_by: Option<GroupBy>,
+    pub filter: Option<FilterExpr>,
+    pub order: Option<Order>,
+    pub limit: Option<usize>,
+}
+
+/// Parse either a full `SELECT ... BY ... WHERE ... LIMIT ...` query, e.g.
+/// `SELECT keys BY process WHERE process = "Code" LIMIT 10`, or a bare
+/// filter expression, e.g. `process = "Code" and count > 100`, which is
+/// equivalent to `SELECT keys WHERE <expr>`.
+pub fn parse(input: &str) -> Result<Query, ParseError> {
+    let tokens = Lexer::new(input).tokenize()?;
+    let mut parser = Parser::new(tokens);
+    if parser.peek().kind == TokenKind::Select {
+        parser.parse_query()
+    } else {
+        let filter = parser.parse_filter_expr()?;
+        if parser.peek().kind != TokenKind::Eof {
+            return Err(parser.unexpected("trailing input after filter expression"));
+        }
+        Ok(Query {
+            metric: Metric::Keys,
+            group_by: None,
+            filter: Some(filter),
+            order: None,
+            limit: None,
+        })
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, ParseError> {
+        if self.peek().kind == kind {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(&format!("expected {:?}", kind)))
+        }
+    }
+
+    fn unexpected(&self, message: &str) -> ParseError {
+        ParseError::Unexpected {
+            offset: self.peek().offset,
+            message: message.to_string(),
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, ParseError> {
+        let offset = self.peek().offset;
+        match self.advance().kind {
+            TokenKind::Ident(s) => Ok(s),
+            other => Err(ParseError::Unexpected {
+                offset,
+                message: format!("expected identifier, found {:?}", other),
+            }),
+        }
+    }
+
+    fn parse_query(&mut self) -> Result<Query, ParseError> {
+        self.expect(TokenKind::Select)?;
+        let metric = match self.expect_ident()?.as_str() {
+            "keys" => Metric::Keys,
+            "freq" => Metric::Freq,
+            "active" => Metric::Active,
+            other => return Err(self.unexpected(&format!("unknown metric '{}'", other))),
+        };
+
+        let group_by = if self.peek().kind == TokenKind::By {
+            self.advance();
+            Some(match self.expect_ident()?.as_str() {
+                "process" => GroupBy::Process,
+                "window" => GroupBy::Window,
+                "key" => GroupBy::Key,
+                "date" => GroupBy::Date,
+                other => {
+                    return Err(self.unexpected(&format!("unknown group-by field '{}'", other)))
+                }
+            })
+        } else {
+            None
+        };
+
+        let filter = if self.peek().kind == TokenKind::Where {
+            self.advance();
+            Some(self.parse_filter_expr()?)
+        } else {
+            None
+        };
+
+        let order = if self.peek().kind == TokenKind::Order {
+            self.advance();
+            self.expect(TokenKind::By)?;
+            let field = self.expect_ident()?;
+            let direction = match self.peek().kind {
+                TokenKind::Desc => {
+                    self.advance();
+                    OrderDirection::Desc
+                }
+                TokenKind::Asc => {
+                    self.advance();
+                    OrderDirection::Asc
+                }
+                _ => OrderDirection::Asc,
+            };
+            Some(Order { field, direction })
+        } else {
+            None
+        };
+
+        let limit = if self.peek().kind == TokenKind::Limit {
+            self.advance();
+            let offset = self.peek().offset;
+            match self.advance().kind {
+                TokenKind::Int(n) => Some(n as usize),
+                other => {
+                    return Err(ParseError::Unexpected {
+                        offset,
+                        message: format!("expected integer after LIMIT, found {:?}", other),
+                    })
+                }
+            }
+        } else {
+            None
+        };
+
+        if self.peek().kind != TokenKind::Eof {
+            return Err(self.unexpected("trailing input after query"));
+        }
+
+        Ok(Query {
+            metric,
+            group_by,
+            filter,
+            order,
+            limit,
+        })
+    }
+
+    /// `or_expr = and_expr (OR and_expr)*`
+    fn parse_filter_expr(&mut self) -> Result<FilterExpr, ParseError> {
+        let mut lhs = self.parse_and_expr()?;
+        while self.peek().kind == TokenKind::Or {
+            self.advance();
+            let rhs = self.parse_and_expr()?;
+            lhs = FilterExpr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// `and_expr = comparison (AND comparison)*`
+    fn parse_and_expr(&mut self) -> Result<FilterExpr, ParseError> {
+        let mut lhs = self.parse_comparison()?;
+        while self.peek().kind == TokenKind::And {
+            self.advance();
+            let rhs = self.parse_comparison()?;
+            lhs = FilterExpr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// `comparison = "(" or_expr ")" | ident BETWEEN int AND int | ident op value`
+    fn parse_comparison(&mut self) -> Result<FilterExpr, ParseError> {
+        if self.peek().kind == TokenKind::LParen {
+            self.advance();
+            let expr = self.parse_filter_expr()?;
+            self.expect(TokenKind::RParen)?;
+            return Ok(expr);
+        }
+
+        let field = self.expect_ident()?;
+
+        if self.peek().kind == TokenKind::Between {
+            self.advance();
+            let low = self.expect_int()?;
+            self.expect(TokenKind::And)?;
+            let high = self.expect_int()?;
+            return Ok(FilterExpr::And(
+                Box::new(FilterExpr::Comparison(Filter {
+                    field: field.clone(),
+                    op: Op::Ge,
+                    value: FilterValue::Int(low),
+                })),
+                Box::new(FilterExpr::Comparison(Filter {
+                    field,
+                    op: Op::Le,
+                    value: FilterValue::Int(high),
+                })),
+            ));
+        }
+
+        let op_offset = self.peek().offset;
+        let op = match self.advance().kind {
+            TokenKind::Match => Op::Match,
+            TokenKind::Eq => Op::Eq,
+            TokenKind::Ne => Op::Ne,
+            TokenKind::Ge => Op::Ge,
+            TokenKind::Le => Op::Le,
+            TokenKind::Gt => Op::Gt,
+            TokenKind::Lt => Op::Lt,
+            other => {
+                return Err(ParseError::Unexpected {
+                    offset: op_offset,
+                    message: format!("expected comparison operator, found {:?}", other),
+                })
+            }
+        };
+        let value_offset = self.peek().offset;
+        let value = match self.advance().kind {
+            TokenKind::Str(s) => FilterValue::Str(s),
+            TokenKind::Int(n) => FilterValue::Int(n),
+            other => {
+                return Err(ParseError::Unexpected {
+                    offset: value_offset,
+                    message: format!("expected a string or integer value, found {:?}", other),
+                })
+            }
+        };
+        Ok(FilterExpr::Comparison(Filter { field, op, value }))
+    }
+
+    fn expect_int(&mut self) -> Result<i64, ParseError> {
+        let offset = self.peek().offset;
+        match self.advance().kind {
+            TokenKind::Int(n) => Ok(n),
+            other => Err(ParseError::Unexpected {
+                offset,
+                message: format!("expected an integer, found {:?}", other),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_select_by_with_where_and_limit() {
+        let query = parse(
+            r#"SELECT keys BY process WHERE process ~ "Code" AND date >= "2024-01-01" LIMIT 10"#,
+        )
+        .unwrap();
+        assert_eq!(query.metric, Metric::Keys);
+        assert_eq!(query.group_by, Some(GroupBy::Process));
+        assert_eq!(
+            query.filter,
+            Some(FilterExpr::And(
+                Box::new(FilterExpr::Comparison(Filter {
+                    field: "process".to_string(),
+                    op: Op::Match,
+                    value: FilterValue::Str("Code".to_string()),
+                })),
+                Box::new(FilterExpr::Comparison(Filter {
+                    field: "date".to_string(),
+                    op: Op::Ge,
+                    value: FilterValue::Str("2024-01-01".to_string()),
+                })),
+            ))
+        );
+        assert_eq!(query.limit, Some(10));
+    }
+
+    #[test]
+    fn test_parses_bare_filter_expression_as_an_implicit_select() {
+        let query = parse(r#"process = "Code" and count > 100"#).unwrap();
+        assert_eq!(query.metric, Metric::Keys);
+        assert_eq!(query.group_by, None);
+        assert_eq!(
+            query.filter,
+            Some(FilterExpr::And(
+                Box::new(FilterExpr::Comparison(Filter {
+                    field: "process".to_string(),
+                    op: Op::Eq,
+                    value: FilterValue::Str("Code".to_string()),
+                })),
+                Box::new(FilterExpr::Comparison(Filter {
+                    field: "count".to_string(),
+                    op: Op::Gt,
+                    value: FilterValue::Int(100),
+                })),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_between_desugars_to_an_and_of_ge_and_le() {
+        let query = parse("hour between 9 and 17").unwrap();
+        assert_eq!(
+            query.filter,
+            Some(FilterExpr::And(
+                Box::new(FilterExpr::Comparison(Filter {
+                    field: "hour".to_string(),
+                    op: Op::Ge,
+                    value: FilterValue::Int(9),
+                })),
+                Box::new(FilterExpr::Comparison(Filter {
+                    field: "hour".to_string(),
+                    op: Op::Le,
+                    value: FilterValue::Int(17),
+                })),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_or_has_lower_precedence_than_and() {
+        let query = parse(r#"process = "Code" and count > 100 or process = "zsh""#).unwrap();
+        let FilterExpr::Or(lhs, rhs) = query.filter.unwrap() else {
+            panic!("expected a top-level Or");
+        };
+        assert!(matches!(*lhs, FilterExpr::And(_, _)));
+        assert!(matches!(*rhs, FilterExpr::Comparison(_)));
+    }
+
+    #[test]
+    fn test_parens_group_an_or_inside_an_and() {
+        let query = parse(r#"process != "Code" and (count > 100 or count < 5)"#).unwrap();
+        let FilterExpr::And(_, rhs) = query.filter.unwrap() else {
+            panic!("expected a top-level And");
+        };
+        assert!(matches!(*rhs, FilterExpr::Or(_, _)));
+    }
+
+    #[test]
+    fn test_parses_order_by_desc() {
+        let query = parse("SELECT freq BY key ORDER BY count DESC").unwrap();
+        assert_eq!(query.metric, Metric::Freq);
+        assert_eq!(
+            query.order,
+            Some(Order {
+                field: "count".to_string(),
+                direction: OrderDirection::Desc
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_metric() {
+        let err = parse("SELECT bogus").unwrap_err();
+        assert!(matches!(err, ParseError::Unexpected { .. }));
+    }
+}
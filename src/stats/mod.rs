@@ -4,16 +4,97 @@ mod milestones;
 
 pub use milestones::{Milestone, MILESTONES};
 
-use chrono::{DateTime, NaiveDate, Utc};
-use std::collections::VecDeque;
+use crate::clock::Clocks;
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+/// Number of completed minutes kept in [`LiveStats::apm_series_minutes`].
+const MINUTE_HISTORY_LEN: usize = 60;
+/// Number of completed hours kept in [`LiveStats::apm_series_hours`].
+const HOUR_HISTORY_LEN: usize = 24;
+
+/// Log-spaced upper bounds of the inter-keystroke interval histogram's first
+/// bins: `<50ms`, `50-100ms`, `100-250ms`, `250-500ms`, `500ms-1s`, and an
+/// open-ended final `>1s` bin past the last boundary. Typing cadence spans
+/// orders of magnitude (a fast digraph vs. a thinking pause), so log spacing
+/// resolves the fast end without needing a bin per millisecond.
+const INTERVAL_BIN_BOUNDARIES: [Duration; 5] = [
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+];
+/// One more bin than there are boundaries, for the open-ended `>1s` tail.
+const INTERVAL_BIN_COUNT: usize = INTERVAL_BIN_BOUNDARIES.len() + 1;
+
+/// Consecutive sub-threshold intervals needed to count as a "burst" of fast
+/// typing rather than just two keystrokes that happened to land close
+/// together. See [`LiveStats::burst_count`].
+const BURST_MIN_RUN: u32 = 3;
+
+/// A keystroke to record, optionally tagged with which key was pressed and
+/// which application/context it happened in. Both tags are plain identifier
+/// strings (matching the `process_name`/key-name strings already used
+/// elsewhere, e.g. `SqliteStorage::record_keystroke`), not a closed enum,
+/// since the set of keys and applications isn't known ahead of time.
+#[derive(Debug, Clone, Default)]
+pub struct KeyEvent {
+    pub key: Option<String>,
+    pub app: Option<String>,
+}
+
+/// Serializable snapshot of the parts of [`LiveStats`] that must survive a
+/// daemon restart: total/streak progress, which milestones were reached, the
+/// per-key/per-application counters, and the completed-bucket APM history
+/// plus its in-progress partial bucket. The rolling APM *window*
+/// (`recent_events`/`recent_contexts`) and `session_start`/`current_minute`/
+/// `current_hour`/`current_day` are deliberately left out — they're anchored
+/// to a monotonic `Instant` that can't be serialized and naturally restart
+/// from zero on resume; a restored partial bucket count simply keeps
+/// accumulating into that fresh zero-based minute/hour/day instead.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionState {
+    pub total_keystrokes: u64,
+    pub current_streak: u32,
+    pub last_active_date: Option<NaiveDate>,
+    pub milestones_reached_at: Vec<Option<DateTime<Utc>>>,
+    #[serde(default)]
+    pub key_counts: HashMap<String, u64>,
+    #[serde(default)]
+    pub app_counts: HashMap<String, u64>,
+    #[serde(default)]
+    pub current_minute_count: u32,
+    #[serde(default)]
+    pub minute_history: VecDeque<u32>,
+    #[serde(default)]
+    pub current_hour_count: u32,
+    #[serde(default)]
+    pub hour_history: VecDeque<u32>,
+    #[serde(default)]
+    pub current_day_count: u32,
+    #[serde(default)]
+    pub day_history: HashMap<NaiveDate, u32>,
+}
+
 /// Rolling window for APM calculation
 pub struct LiveStats {
     // Circular buffer of timestamps (last N seconds)
     recent_events: VecDeque<Instant>,
+    // Parallel to `recent_events`: the `app` tag (if any) recorded alongside
+    // each timestamp, so `apm_for` can filter the same rolling window by
+    // context without keeping a second pruned buffer per app.
+    recent_contexts: VecDeque<Option<String>>,
     window_duration: Duration,
 
+    // Per-key and per-application breakdowns, accumulated for the whole
+    // session (not windowed).
+    key_counts: HashMap<String, u64>,
+    app_counts: HashMap<String, u64>,
+
     // Cumulative stats
     total_keystrokes: u64,
     session_start: Instant,
@@ -21,38 +102,136 @@ pub struct LiveStats {
     // Streak tracking
     current_streak: u32,
     last_active_date: Option<NaiveDate>,
+    /// Local timezone the "logical date" used for streaks is computed in.
+    /// Defaults to UTC.
+    utc_offset: FixedOffset,
+    /// How far past local midnight the day rolls over, e.g. 4 hours for
+    /// night owls who want a 4am cutoff instead of midnight. Defaults to
+    /// zero (plain local-midnight rollover).
+    day_start_offset: Duration,
 
     // Milestones
     milestones_reached: Vec<Milestone>,
+
+    // Time-bucketed APM history: completed minutes roll up into completed
+    // hours, which roll up into a per-day map. Gaps are zero-filled as they
+    // roll so the series stays contiguous even across idle periods. See
+    // `record`/`roll_to_minute`.
+    current_minute: u64,
+    current_minute_count: u32,
+    minute_history: VecDeque<u32>,
+
+    current_hour: u64,
+    current_hour_count: u32,
+    hour_history: VecDeque<u32>,
+
+    current_day: u64,
+    current_day_count: u32,
+    day_history: HashMap<NaiveDate, u32>,
+    session_start_date: NaiveDate,
+
+    // Inter-keystroke interval tracking: cadence is summarized into a
+    // fixed-size histogram (see `INTERVAL_BIN_BOUNDARIES`) rather than kept as
+    // raw samples, since a long session can produce far more keystrokes than
+    // would be reasonable to retain individually. Burst runs are tracked the
+    // same way, incrementally, one running streak length per boundary.
+    last_event_at: Option<Instant>,
+    interval_histogram: [u64; INTERVAL_BIN_COUNT],
+    burst_runs: [u32; INTERVAL_BIN_BOUNDARIES.len()],
+    burst_counts: [u32; INTERVAL_BIN_BOUNDARIES.len()],
+
+    clocks: Arc<dyn Clocks>,
 }
 
 impl LiveStats {
-    pub fn new(window_secs: u64) -> Self {
+    pub fn new(window_secs: u64, clocks: Arc<dyn Clocks>) -> Self {
+        Self::with_day_boundary(window_secs, clocks, FixedOffset::east_opt(0).unwrap(), Duration::ZERO)
+    }
+
+    /// Like [`LiveStats::new`], but computes streak/day-bucket dates in
+    /// `utc_offset` with the day rolling over `day_start_offset` past local
+    /// midnight instead of at UTC midnight. Use this for users west of UTC
+    /// (whose late-night sessions would otherwise count toward the wrong
+    /// day) or night owls who want a 4am cutoff rather than midnight.
+    pub fn with_day_boundary(
+        window_secs: u64,
+        clocks: Arc<dyn Clocks>,
+        utc_offset: FixedOffset,
+        day_start_offset: Duration,
+    ) -> Self {
+        let session_start = clocks.monotonic_now();
+        let session_start_date = logical_date(clocks.now(), utc_offset, day_start_offset);
         Self {
             recent_events: VecDeque::with_capacity(1000),
+            recent_contexts: VecDeque::with_capacity(1000),
             window_duration: Duration::from_secs(window_secs),
+            key_counts: HashMap::new(),
+            app_counts: HashMap::new(),
             total_keystrokes: 0,
-            session_start: Instant::now(),
+            session_start,
             current_streak: 0,
             last_active_date: None,
+            utc_offset,
+            day_start_offset,
             milestones_reached: MILESTONES.to_vec(),
+            current_minute: 0,
+            current_minute_count: 0,
+            minute_history: VecDeque::with_capacity(MINUTE_HISTORY_LEN),
+            current_hour: 0,
+            current_hour_count: 0,
+            hour_history: VecDeque::with_capacity(HOUR_HISTORY_LEN),
+            current_day: 0,
+            current_day_count: 0,
+            day_history: HashMap::new(),
+            session_start_date,
+            last_event_at: None,
+            interval_histogram: [0; INTERVAL_BIN_COUNT],
+            burst_runs: [0; INTERVAL_BIN_BOUNDARIES.len()],
+            burst_counts: [0; INTERVAL_BIN_BOUNDARIES.len()],
+            clocks,
         }
     }
 
-    /// Record a keystroke and check for milestones
+    /// Record a keystroke and check for milestones. Convenience for callers
+    /// that don't track which key or application it belongs to; see
+    /// [`LiveStats::record_event`].
     pub fn record(&mut self) -> Option<&Milestone> {
-        let now = Instant::now();
+        self.record_event(KeyEvent::default())
+    }
+
+    /// Record a keystroke, optionally tagged with the key pressed and the
+    /// application it was pressed in, and check for milestones.
+    pub fn record_event(&mut self, event: KeyEvent) -> Option<&Milestone> {
+        let now = self.clocks.monotonic_now();
+        if let Some(last) = self.last_event_at {
+            self.record_interval(now - last);
+        }
+        self.last_event_at = Some(now);
+
         self.recent_events.push_back(now);
+        self.recent_contexts.push_back(event.app.clone());
         self.total_keystrokes += 1;
 
+        if let Some(key) = &event.key {
+            *self.key_counts.entry(key.clone()).or_insert(0) += 1;
+        }
+        if let Some(app) = &event.app {
+            *self.app_counts.entry(app.clone()).or_insert(0) += 1;
+        }
+
         // Prune old events outside window
         let cutoff = now - self.window_duration;
         while self.recent_events.front().is_some_and(|&t| t < cutoff) {
             self.recent_events.pop_front();
+            self.recent_contexts.pop_front();
         }
 
+        let minute = (now - self.session_start).as_secs() / 60;
+        self.roll_to_minute(minute);
+        self.current_minute_count += 1;
+
         // Update streak
-        let today = Utc::now().date_naive();
+        let today = self.logical_date(self.clocks.now());
         if let Some(last_date) = self.last_active_date {
             if today != last_date {
                 let days_diff = (today - last_date).num_days();
@@ -68,15 +247,112 @@ impl LiveStats {
         self.last_active_date = Some(today);
 
         // Check milestones
+        let reached_at = self.clocks.now();
         self.milestones_reached
             .iter_mut()
             .find(|m| m.reached_at.is_none() && self.total_keystrokes >= m.threshold)
             .map(|m| {
-                m.reached_at = Some(Utc::now());
+                m.reached_at = Some(reached_at);
                 &*m
             })
     }
 
+    /// Finalize every minute from `self.current_minute` up to (but not
+    /// including) `minute`, zero-filling any that had no events, then roll
+    /// completed minutes into hours and completed hours into days the same
+    /// way.
+    fn roll_to_minute(&mut self, minute: u64) {
+        while self.current_minute < minute {
+            self.finalize_minute(self.current_minute, self.current_minute_count);
+            self.current_minute_count = 0;
+            self.current_minute += 1;
+        }
+    }
+
+    fn finalize_minute(&mut self, minute: u64, count: u32) {
+        if self.minute_history.len() == MINUTE_HISTORY_LEN {
+            self.minute_history.pop_front();
+        }
+        self.minute_history.push_back(count);
+
+        let hour = minute / 60;
+        while self.current_hour < hour {
+            self.finalize_hour(self.current_hour, self.current_hour_count);
+            self.current_hour_count = 0;
+            self.current_hour += 1;
+        }
+        self.current_hour_count += count;
+    }
+
+    fn finalize_hour(&mut self, hour: u64, count: u32) {
+        if self.hour_history.len() == HOUR_HISTORY_LEN {
+            self.hour_history.pop_front();
+        }
+        self.hour_history.push_back(count);
+
+        let day = hour / 24;
+        while self.current_day < day {
+            self.finalize_day(self.current_day, self.current_day_count);
+            self.current_day_count = 0;
+            self.current_day += 1;
+        }
+        self.current_day_count += count;
+    }
+
+    fn finalize_day(&mut self, day: u64, count: u32) {
+        let date = self.session_start_date + chrono::Duration::days(day as i64);
+        self.day_history.insert(date, count);
+    }
+
+    /// Bucket one inter-keystroke interval into the histogram, and update
+    /// each boundary's running burst streak: every boundary the interval
+    /// falls under extends its streak (counting a burst once the streak
+    /// reaches `BURST_MIN_RUN`), every other boundary's streak resets.
+    fn record_interval(&mut self, interval: Duration) {
+        let bin = INTERVAL_BIN_BOUNDARIES
+            .iter()
+            .position(|&boundary| interval < boundary)
+            .unwrap_or(INTERVAL_BIN_BOUNDARIES.len());
+        self.interval_histogram[bin] += 1;
+
+        for (i, &boundary) in INTERVAL_BIN_BOUNDARIES.iter().enumerate() {
+            if interval < boundary {
+                self.burst_runs[i] += 1;
+                if self.burst_runs[i] == BURST_MIN_RUN {
+                    self.burst_counts[i] += 1;
+                }
+            } else {
+                self.burst_runs[i] = 0;
+            }
+        }
+    }
+
+    /// Completed per-minute event counts, oldest first, for up to the last
+    /// `MINUTE_HISTORY_LEN` minutes. The currently-accumulating minute is not
+    /// included until it finalizes.
+    pub fn apm_series_minutes(&self) -> Vec<u32> {
+        self.minute_history.iter().copied().collect()
+    }
+
+    /// Completed per-hour event counts, oldest first, for up to the last
+    /// `HOUR_HISTORY_LEN` hours.
+    pub fn apm_series_hours(&self) -> Vec<u32> {
+        self.hour_history.iter().copied().collect()
+    }
+
+    /// Completed per-day event counts. Day N is the Nth 24-hour span since
+    /// session start, keyed by `session_start_date` plus N days; the spans
+    /// are not aligned to local midnight or the day-start rollover.
+    pub fn apm_series_days(&self) -> &HashMap<NaiveDate, u32> {
+        &self.day_history
+    }
+
+    /// The busiest completed minute recorded so far, i.e. the highest
+    /// actions-per-minute seen in `apm_series_minutes`.
+    pub fn peak_apm(&self) -> u32 {
+        self.minute_history.iter().copied().max().unwrap_or(0)
+    }
+
     /// Actions Per Minute (rolling window)
     pub fn apm(&self) -> f64 {
         let count = self.recent_events.len() as f64;
@@ -84,9 +360,94 @@ impl LiveStats {
         count / window_mins
     }
 
+    /// Actions Per Minute for just the events tagged with `context`
+    /// (e.g. a single application), over the same rolling window as `apm`.
+    pub fn apm_for(&self, context: &str) -> f64 {
+        let count = self
+            .recent_contexts
+            .iter()
+            .filter(|app| app.as_deref() == Some(context))
+            .count() as f64;
+        let window_mins = self.window_duration.as_secs_f64() / 60.0;
+        count / window_mins
+    }
+
+    /// The `n` most-pressed keys this session, most-pressed first.
+    pub fn top_keys(&self, n: usize) -> Vec<(String, u64)> {
+        top_n(&self.key_counts, n)
+    }
+
+    /// The `n` applications with the most keystrokes this session, busiest
+    /// first.
+    pub fn top_apps(&self, n: usize) -> Vec<(String, u64)> {
+        top_n(&self.app_counts, n)
+    }
+
+    /// Raw inter-keystroke interval histogram: counts for `<50ms`,
+    /// `50-100ms`, `100-250ms`, `250-500ms`, `500ms-1s`, and `>1s`, in that
+    /// order. See [`LiveStats::interval_percentile`] for a queryable summary.
+    pub fn interval_histogram(&self) -> [u64; INTERVAL_BIN_COUNT] {
+        self.interval_histogram
+    }
+
+    /// Estimate the `p`th percentile (`0.0..=1.0`) inter-keystroke interval
+    /// by linearly interpolating across the histogram's cumulative bucket
+    /// counts, assuming intervals are spread uniformly within whichever bin
+    /// the percentile falls in. Returns `Duration::ZERO` with no recorded
+    /// intervals. A percentile landing in the open-ended `>1s` tail bin
+    /// reports that bin's lower bound rather than an invented upper bound.
+    pub fn interval_percentile(&self, p: f64) -> Duration {
+        let total: u64 = self.interval_histogram.iter().sum();
+        if total == 0 {
+            return Duration::ZERO;
+        }
+        let target = ((p.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+
+        let mut cumulative = 0u64;
+        for (i, &count) in self.interval_histogram.iter().enumerate() {
+            let bin_start = cumulative;
+            cumulative += count;
+            if target > cumulative && i + 1 < self.interval_histogram.len() {
+                continue;
+            }
+
+            let lower = if i == 0 {
+                Duration::ZERO
+            } else {
+                INTERVAL_BIN_BOUNDARIES[i - 1]
+            };
+            let Some(&upper) = INTERVAL_BIN_BOUNDARIES.get(i) else {
+                return lower;
+            };
+            if count == 0 {
+                return lower;
+            }
+            let fraction = (target - bin_start) as f64 / count as f64;
+            return lower + upper.saturating_sub(lower).mul_f64(fraction);
+        }
+        unreachable!("loop always returns before exhausting a non-empty histogram")
+    }
+
+    /// Number of times a run of at least [`BURST_MIN_RUN`] consecutive
+    /// intervals under `threshold` has occurred, i.e. sustained fast typing
+    /// rather than isolated quick keystrokes. `threshold` must be one of
+    /// [`INTERVAL_BIN_BOUNDARIES`] (the same bins used for
+    /// [`LiveStats::interval_histogram`]); any other value returns 0, since
+    /// burst runs are tracked incrementally per boundary rather than
+    /// recomputed from stored intervals.
+    pub fn burst_count(&self, threshold: Duration) -> u32 {
+        match INTERVAL_BIN_BOUNDARIES
+            .iter()
+            .position(|&boundary| boundary == threshold)
+        {
+            Some(i) => self.burst_counts[i],
+            None => 0,
+        }
+    }
+
     /// Keys per second (instantaneous, last 5 seconds)
     pub fn kps(&self) -> f64 {
-        let now = Instant::now();
+        let now = self.clocks.monotonic_now();
         let cutoff = now - Duration::from_secs(5);
         let recent = self.recent_events.iter().filter(|&&t| t >= cutoff).count();
         recent as f64 / 5.0
@@ -94,7 +455,7 @@ impl LiveStats {
 
     /// Session duration
     pub fn session_duration(&self) -> Duration {
-        self.session_start.elapsed()
+        self.clocks.monotonic_now() - self.session_start
     }
 
     /// Total keystrokes
@@ -124,16 +485,151 @@ impl LiveStats {
             .filter(|m| m.reached_at.is_some())
             .max_by_key(|m| m.reached_at)
     }
+
+    /// Capture the resumable parts of this session's state for periodic
+    /// persistence via `SqliteStorage::save_session_state`.
+    pub fn snapshot(&self) -> SessionState {
+        SessionState {
+            total_keystrokes: self.total_keystrokes,
+            current_streak: self.current_streak,
+            last_active_date: self.last_active_date,
+            milestones_reached_at: self
+                .milestones_reached
+                .iter()
+                .map(|m| m.reached_at)
+                .collect(),
+            key_counts: self.key_counts.clone(),
+            app_counts: self.app_counts.clone(),
+            current_minute_count: self.current_minute_count,
+            minute_history: self.minute_history.clone(),
+            current_hour_count: self.current_hour_count,
+            hour_history: self.hour_history.clone(),
+            current_day_count: self.current_day_count,
+            day_history: self.day_history.clone(),
+        }
+    }
+
+    /// Continue counting into a previously snapshotted state instead of
+    /// starting from zero, e.g. after resuming a session that never got to
+    /// call `end_session`.
+    pub fn restore(&mut self, state: SessionState) {
+        self.total_keystrokes = state.total_keystrokes;
+        self.current_streak = state.current_streak;
+        self.last_active_date = state.last_active_date;
+        for (milestone, reached_at) in self
+            .milestones_reached
+            .iter_mut()
+            .zip(state.milestones_reached_at)
+        {
+            milestone.reached_at = reached_at;
+        }
+        self.key_counts = state.key_counts;
+        self.app_counts = state.app_counts;
+        self.current_minute_count = state.current_minute_count;
+        self.minute_history = state.minute_history;
+        self.current_hour_count = state.current_hour_count;
+        self.hour_history = state.hour_history;
+        self.current_day_count = state.current_day_count;
+        self.day_history = state.day_history;
+    }
+
+    /// Break the streak if `last_active_date` is more than a day in the
+    /// past. `record`'s inline streak check only runs on the *next* keystroke
+    /// after a restore, so without this a stale streak would keep reporting
+    /// as unbroken until the user types again, even if they missed a whole
+    /// day in between.
+    fn reconcile_streak(&mut self) {
+        let Some(last_date) = self.last_active_date else {
+            return;
+        };
+        let today = self.logical_date(self.clocks.now());
+        if (today - last_date).num_days() > 1 {
+            self.current_streak = 0;
+        }
+    }
+
+    /// The logical date `now` falls on, per this instance's `utc_offset` and
+    /// `day_start_offset`. See [`logical_date`].
+    fn logical_date(&self, now: DateTime<Utc>) -> NaiveDate {
+        logical_date(now, self.utc_offset, self.day_start_offset)
+    }
+
+    /// Parse a bare `YYYY-MM-DD` date as the instant its logical day begins
+    /// under this instance's `utc_offset`/`day_start_offset` (local midnight
+    /// plus `day_start_offset`, converted to UTC), so a backfilled/imported
+    /// date given without a time component lines up with this instance's
+    /// rollover instead of UTC midnight.
+    pub fn parse_logical_date(&self, date: &str) -> Option<DateTime<Utc>> {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+        let start_of_day = date.and_hms_opt(0, 0, 0)?
+            + chrono::Duration::from_std(self.day_start_offset).ok()?;
+        let local = self.utc_offset.from_local_datetime(&start_of_day).single()?;
+        Some(local.with_timezone(&Utc))
+    }
+
+    /// Persist the resumable parts of this session's state (see
+    /// [`LiveStats::snapshot`]) as JSON at `path`.
+    pub fn save(&self, path: &std::path::Path) -> crate::Result<()> {
+        let json = serde_json::to_vec_pretty(&self.snapshot())?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Load a session previously written by [`LiveStats::save`], restoring
+    /// cumulative progress but starting a fresh rolling window and
+    /// `session_start`. Falls back to a brand-new session if `path` doesn't
+    /// exist. Immediately re-checks the streak against today's date, so a day
+    /// missed entirely while the daemon wasn't running still breaks it.
+    pub fn load(
+        path: &std::path::Path,
+        window_secs: u64,
+        clocks: Arc<dyn Clocks>,
+    ) -> crate::Result<Self> {
+        let mut stats = Self::new(window_secs, clocks);
+        if path.exists() {
+            let json = std::fs::read(path)?;
+            let state: SessionState = serde_json::from_slice(&json)?;
+            stats.restore(state);
+            stats.reconcile_streak();
+        }
+        Ok(stats)
+    }
+}
+
+/// The "logical date" `now` falls on: the wall clock in `utc_offset`, minus
+/// `day_start_offset`, then truncated to a date. A `day_start_offset` of 4
+/// hours means a 3am session still counts toward yesterday.
+fn logical_date(now: DateTime<Utc>, utc_offset: FixedOffset, day_start_offset: Duration) -> NaiveDate {
+    let local = now.with_timezone(&utc_offset);
+    let adjusted = local
+        - chrono::Duration::from_std(day_start_offset).expect("day_start_offset fits in chrono::Duration");
+    adjusted.date_naive()
+}
+
+/// The `n` highest-count entries of `counts`, highest first, breaking ties
+/// by name for a deterministic order.
+fn top_n(counts: &HashMap<String, u64>, n: usize) -> Vec<(String, u64)> {
+    let mut entries: Vec<(String, u64)> = counts.iter().map(|(k, v)| (k.clone(), *v)).collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    entries.truncate(n);
+    entries
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::thread;
+    use crate::clock::SimulatedClocks;
+
+    fn simulated_clocks() -> Arc<SimulatedClocks> {
+        let start = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        Arc::new(SimulatedClocks::new(start))
+    }
 
     #[test]
     fn test_initial_state() {
-        let stats = LiveStats::new(60);
+        let stats = LiveStats::new(60, simulated_clocks());
         assert_eq!(stats.total(), 0);
         assert_eq!(stats.events_in_window(), 0);
         assert_eq!(stats.apm(), 0.0);
@@ -141,7 +637,7 @@ mod tests {
 
     #[test]
     fn test_record_increments_total() {
-        let mut stats = LiveStats::new(60);
+        let mut stats = LiveStats::new(60, simulated_clocks());
         stats.record();
         stats.record();
         stats.record();
@@ -150,7 +646,7 @@ mod tests {
 
     #[test]
     fn test_apm_calculation() {
-        let mut stats = LiveStats::new(60); // 60 second window
+        let mut stats = LiveStats::new(60, simulated_clocks()); // 60 second window
 
         // Record 60 events
         for _ in 0..60 {
@@ -164,13 +660,14 @@ mod tests {
     #[test]
     fn test_window_pruning() {
         // Use a very short window for testing
-        let mut stats = LiveStats::new(1); // 1 second window
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(1, clocks.clone()); // 1 second window
 
         stats.record();
         assert_eq!(stats.events_in_window(), 1);
 
-        // Wait for events to expire
-        thread::sleep(Duration::from_millis(1100));
+        // Advance the simulated clock past the window instead of sleeping.
+        clocks.advance(Duration::from_millis(1100));
 
         // Record new event to trigger pruning
         stats.record();
@@ -183,7 +680,7 @@ mod tests {
 
     #[test]
     fn test_milestone_detection() {
-        let mut stats = LiveStats::new(60);
+        let mut stats = LiveStats::new(60, simulated_clocks());
 
         // Record enough keystrokes to hit first milestone (1000)
         for _ in 0..1000 {
@@ -194,4 +691,396 @@ mod tests {
         assert!(latest.is_some());
         assert_eq!(latest.unwrap().threshold, 1000);
     }
+
+    #[test]
+    fn test_apm_series_minutes_finalizes_completed_minutes_only() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+
+        stats.record();
+        stats.record();
+        assert!(stats.apm_series_minutes().is_empty());
+
+        clocks.advance(Duration::from_secs(60));
+        stats.record();
+
+        assert_eq!(stats.apm_series_minutes(), vec![2]);
+        assert_eq!(stats.peak_apm(), 2);
+    }
+
+    #[test]
+    fn test_apm_series_minutes_zero_fills_idle_gaps() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+
+        stats.record();
+        clocks.advance(Duration::from_secs(3 * 60));
+        stats.record();
+        stats.record();
+        clocks.advance(Duration::from_secs(60));
+        stats.record();
+
+        assert_eq!(stats.apm_series_minutes(), vec![1, 0, 0, 2]);
+        assert_eq!(stats.peak_apm(), 2);
+    }
+
+    #[test]
+    fn test_apm_series_hours_rolls_up_from_completed_minutes() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+
+        // One event a minute; hour 0 only finalizes once a minute in hour 1
+        // finalizes behind it, which takes one minute past the 60th.
+        for _ in 0..62 {
+            stats.record();
+            clocks.advance(Duration::from_secs(60));
+        }
+        assert_eq!(stats.apm_series_hours(), vec![60]);
+    }
+
+    #[test]
+    fn test_apm_series_days_rolls_up_from_completed_hours() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+
+        // Mirrors the hour rollup's "one past the boundary" requirement, one
+        // level up: day 0 only finalizes once an hour in day 1 finalizes.
+        for _ in 0..1502 {
+            stats.record();
+            clocks.advance(Duration::from_secs(60));
+        }
+
+        let days = stats.apm_series_days();
+        assert_eq!(days.len(), 1);
+        assert_eq!(*days.values().next().unwrap(), 24 * 60);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trips_cumulative_state() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+        for _ in 0..1500 {
+            stats.record();
+        }
+
+        let path = std::env::temp_dir().join("kstrk-test-round-trip.json");
+        stats.save(&path).unwrap();
+
+        let loaded = LiveStats::load(&path, 60, clocks.clone()).unwrap();
+        assert_eq!(loaded.total(), 1500);
+        assert_eq!(loaded.streak(), 1);
+        assert_eq!(loaded.latest_milestone().unwrap().threshold, 1000);
+        // A fresh rolling window and session start, not restored from disk.
+        assert_eq!(loaded.events_in_window(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_save_and_load_round_trips_key_app_counters_and_partial_buckets() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+        for _ in 0..5 {
+            stats.record_event(KeyEvent {
+                key: Some("a".to_string()),
+                app: Some("Code".to_string()),
+            });
+        }
+        // Not yet a full minute, so this only lives in the partial bucket.
+        clocks.advance(Duration::from_secs(3 * 60));
+        stats.record_event(KeyEvent {
+            key: Some("b".to_string()),
+            app: Some("Terminal".to_string()),
+        });
+
+        let path = std::env::temp_dir().join("kstrk-test-counters-round-trip.json");
+        stats.save(&path).unwrap();
+
+        let loaded = LiveStats::load(&path, 60, clocks).unwrap();
+        assert_eq!(
+            loaded.top_keys(2),
+            vec![("a".to_string(), 5), ("b".to_string(), 1)]
+        );
+        assert_eq!(loaded.top_apps(1), vec![("Code".to_string(), 5)]);
+        assert_eq!(loaded.apm_series_minutes(), vec![5, 0, 0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_missing_file_starts_a_fresh_session() {
+        let clocks = simulated_clocks();
+        let path = std::env::temp_dir().join("kstrk-test-does-not-exist.json");
+        let _ = std::fs::remove_file(&path);
+
+        let stats = LiveStats::load(&path, 60, clocks).unwrap();
+        assert_eq!(stats.total(), 0);
+        assert_eq!(stats.streak(), 0);
+    }
+
+    #[test]
+    fn test_load_breaks_streak_after_a_fully_missed_day() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+        stats.record();
+        assert_eq!(stats.streak(), 1);
+
+        let path = std::env::temp_dir().join("kstrk-test-missed-day.json");
+        stats.save(&path).unwrap();
+
+        // Two days pass with the daemon not running at all.
+        clocks.advance(Duration::from_secs(2 * 24 * 3600));
+
+        let loaded = LiveStats::load(&path, 60, clocks).unwrap();
+        assert_eq!(loaded.streak(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_keeps_streak_when_last_active_yesterday() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+        stats.record();
+        assert_eq!(stats.streak(), 1);
+
+        let path = std::env::temp_dir().join("kstrk-test-one-day-gap.json");
+        stats.save(&path).unwrap();
+
+        clocks.advance(Duration::from_secs(24 * 3600));
+
+        let loaded = LiveStats::load(&path, 60, clocks).unwrap();
+        assert_eq!(loaded.streak(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_record_event_tracks_total_like_record() {
+        let mut stats = LiveStats::new(60, simulated_clocks());
+        stats.record_event(KeyEvent {
+            key: Some("a".to_string()),
+            app: Some("Code".to_string()),
+        });
+        stats.record();
+        assert_eq!(stats.total(), 2);
+    }
+
+    #[test]
+    fn test_top_keys_and_top_apps_rank_by_count() {
+        let mut stats = LiveStats::new(60, simulated_clocks());
+        for _ in 0..5 {
+            stats.record_event(KeyEvent {
+                key: Some("a".to_string()),
+                app: Some("Code".to_string()),
+            });
+        }
+        for _ in 0..3 {
+            stats.record_event(KeyEvent {
+                key: Some("b".to_string()),
+                app: Some("Terminal".to_string()),
+            });
+        }
+        stats.record_event(KeyEvent {
+            key: Some("c".to_string()),
+            app: Some("Terminal".to_string()),
+        });
+
+        assert_eq!(
+            stats.top_keys(2),
+            vec![("a".to_string(), 5), ("b".to_string(), 3)]
+        );
+        assert_eq!(stats.top_apps(1), vec![("Terminal".to_string(), 4)]);
+    }
+
+    #[test]
+    fn test_apm_for_filters_by_context() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+        for _ in 0..4 {
+            stats.record_event(KeyEvent {
+                key: None,
+                app: Some("Code".to_string()),
+            });
+        }
+        for _ in 0..2 {
+            stats.record_event(KeyEvent {
+                key: None,
+                app: Some("Terminal".to_string()),
+            });
+        }
+
+        assert_eq!(stats.apm_for("Code"), 4.0);
+        assert_eq!(stats.apm_for("Terminal"), 2.0);
+        assert_eq!(stats.apm_for("Unknown"), 0.0);
+        assert_eq!(stats.apm(), 6.0);
+    }
+
+    #[test]
+    fn test_milestone_reached_at_is_stamped_with_the_injected_clock() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+
+        clocks.advance(Duration::from_secs(3600));
+        for _ in 0..1000 {
+            stats.record();
+        }
+
+        let latest = stats.latest_milestone().unwrap();
+        assert_eq!(latest.reached_at, Some(clocks.now()));
+    }
+
+    #[test]
+    fn test_day_boundary_defaults_to_utc_midnight() {
+        // 2024-01-01T23:30:00Z is still 2024-01-01 with no offset/rollover.
+        let start = DateTime::parse_from_rfc3339("2024-01-01T23:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let clocks = Arc::new(SimulatedClocks::new(start));
+        let mut stats = LiveStats::new(60, clocks.clone());
+        stats.record();
+        assert_eq!(stats.streak(), 1);
+
+        // An hour later it's 2024-01-02 UTC: a new day, streak continues.
+        clocks.advance(Duration::from_secs(3600));
+        stats.record();
+        assert_eq!(stats.streak(), 2);
+    }
+
+    #[test]
+    fn test_day_boundary_respects_negative_utc_offset() {
+        // 2024-01-02T02:00:00Z is 2024-01-01 20:00 in UTC-6 (e.g. US Central).
+        let start = DateTime::parse_from_rfc3339("2024-01-02T02:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let clocks = Arc::new(SimulatedClocks::new(start));
+        let offset = FixedOffset::west_opt(6 * 3600).unwrap();
+        let mut stats = LiveStats::with_day_boundary(60, clocks.clone(), offset, Duration::ZERO);
+        stats.record();
+        assert_eq!(stats.streak(), 1);
+
+        // 4 hours later it's 2024-01-02 00:00 local: a new local day.
+        clocks.advance(Duration::from_secs(4 * 3600));
+        stats.record();
+        assert_eq!(stats.streak(), 2);
+    }
+
+    #[test]
+    fn test_day_start_offset_delays_rollover_past_local_midnight() {
+        // 00:30 local, with a 4am rollover: still counts as the previous day.
+        let start = DateTime::parse_from_rfc3339("2024-01-02T00:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let clocks = Arc::new(SimulatedClocks::new(start));
+        let mut stats = LiveStats::with_day_boundary(
+            60,
+            clocks.clone(),
+            FixedOffset::east_opt(0).unwrap(),
+            Duration::from_secs(4 * 3600),
+        );
+        stats.record();
+        assert_eq!(stats.streak(), 1);
+
+        // Still before the 4am rollover: same logical day, streak unchanged.
+        clocks.advance(Duration::from_secs(3 * 3600));
+        stats.record();
+        assert_eq!(stats.streak(), 1);
+
+        // Past the 4am rollover: new logical day.
+        clocks.advance(Duration::from_secs(2 * 3600));
+        stats.record();
+        assert_eq!(stats.streak(), 2);
+    }
+
+    #[test]
+    fn test_parse_logical_date_aligns_with_the_configured_rollover() {
+        let clocks = simulated_clocks();
+        let stats = LiveStats::with_day_boundary(
+            60,
+            clocks,
+            FixedOffset::west_opt(5 * 3600).unwrap(),
+            Duration::from_secs(4 * 3600),
+        );
+
+        // 2024-03-10 local midnight + 4h rollover + 5h (UTC-5) = 09:00 UTC.
+        let parsed = stats.parse_logical_date("2024-03-10").unwrap();
+        let expected = DateTime::parse_from_rfc3339("2024-03-10T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn test_interval_histogram_buckets_intervals_by_duration() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+
+        stats.record(); // no interval yet: the first event
+        clocks.advance(Duration::from_millis(30));
+        stats.record(); // 30ms -> <50ms
+        clocks.advance(Duration::from_millis(80));
+        stats.record(); // 80ms -> 50-100ms
+        clocks.advance(Duration::from_secs(2));
+        stats.record(); // 2s -> >1s
+
+        assert_eq!(stats.interval_histogram(), [1, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_interval_percentile_interpolates_within_a_bin() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+
+        stats.record();
+        for _ in 0..4 {
+            clocks.advance(Duration::from_millis(10));
+            stats.record();
+        }
+        // 4 intervals, all under the 50ms boundary.
+
+        assert_eq!(stats.interval_percentile(0.5), Duration::from_millis(25));
+        assert_eq!(stats.interval_percentile(1.0), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_interval_percentile_with_no_intervals_is_zero() {
+        let stats = LiveStats::new(60, simulated_clocks());
+        assert_eq!(stats.interval_percentile(0.5), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_burst_count_counts_runs_of_consecutive_fast_intervals() {
+        let clocks = simulated_clocks();
+        let mut stats = LiveStats::new(60, clocks.clone());
+        let threshold = Duration::from_millis(50);
+
+        stats.record();
+        for _ in 0..5 {
+            clocks.advance(Duration::from_millis(10));
+            stats.record();
+        }
+        // A run of 5 consecutive sub-threshold intervals: one burst.
+        assert_eq!(stats.burst_count(threshold), 1);
+
+        // A slow interval breaks the run.
+        clocks.advance(Duration::from_secs(2));
+        stats.record();
+        assert_eq!(stats.burst_count(threshold), 1);
+
+        // A fresh run of 3 starts a second burst.
+        for _ in 0..3 {
+            clocks.advance(Duration::from_millis(10));
+            stats.record();
+        }
+        assert_eq!(stats.burst_count(threshold), 2);
+    }
+
+    #[test]
+    fn test_burst_count_with_a_threshold_outside_the_histogram_bins_is_zero() {
+        let mut stats = LiveStats::new(60, simulated_clocks());
+        for _ in 0..5 {
+            stats.record();
+        }
+        assert_eq!(stats.burst_count(Duration::from_millis(42)), 0);
+    }
 }
@@ -1,142 +1,830 @@
 //! Storage module for persisting keystroke data using SQLite
+//!
+//! Keystroke aggregates are kept in an append-only operation log (see the
+//! "sync" section below) rather than mutated in place, so two devices'
+//! databases can be merged without coordination. When encryption is enabled
+//! (see the "encryption" section), only the captured process/window text
+//! carried by each op is encrypted at rest; the numeric counters stay in
+//! cleartext so summaries keep working without the passphrase.
 
-use crate::capture::KeyEvent;
-use chrono::{DateTime, Utc};
-use rusqlite::{Connection, Result};
+use crate::clock::{Clocks, SystemClocks};
+use argon2::Argon2;
+use chacha20poly1305::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rusqlite::{Connection, OptionalExtension, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+/// Identifier for a row in the `sessions` table.
+pub type SessionId = i64;
+
+/// Errors from paths that round-trip through `rmp-serde` (msgpack) on top of
+/// the usual SQLite error surface: session-state snapshot/resume, the
+/// op-log used for cross-device sync, and at-rest encryption of op text.
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("Database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("Failed to encode state: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+
+    #[error("Failed to decode state: {0}")]
+    Decode(#[from] rmp_serde::decode::Error),
+
+    #[error("a passphrase is required to read or write this encrypted database")]
+    PassphraseRequired,
+
+    #[error("key derivation failed: {0}")]
+    KeyDerivation(String),
+
+    #[error("encrypting or decrypting row text failed (wrong passphrase, or corrupt row)")]
+    Crypto,
+
+    #[error(
+        "database schema is at version {on_disk}, but this build only understands up to \
+         version {supported} — upgrade kstrk before opening this database"
+    )]
+    UnsupportedSchemaVersion { on_disk: i64, supported: i64 },
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Adapts a [`StorageError`] to `rusqlite::Error` so msgpack/encryption-backed
+/// methods can keep the plain `Result<T>` (`rusqlite::Result<T>`) signature
+/// the rest of this module's callers already depend on.
+fn to_sqlite_err(e: StorageError) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+}
+
+/// How often (in ops) the cleartext counters are folded into a new
+/// `checkpoints` row, so `current_state` only has to replay the tail of the
+/// op log instead of the whole thing. Only `total_keystrokes`/`per_hour` are
+/// ever checkpointed — see the module docs on why process/window text isn't.
+const KEEP_STATE_EVERY: u64 = 64;
+
+/// The captured process/window text for one keystroke op. Msgpacked and,
+/// when encryption is enabled, sealed with XChaCha20-Poly1305 before it's
+/// written to `ops.text_payload`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TextFields {
+    process: String,
+    window: String,
+}
+
+/// The cleartext-only aggregate state folded out of the op log: total
+/// keystrokes and hourly totals. This is what gets serialized into a
+/// `checkpoints` row — deliberately excludes the per-process/per-window
+/// breakdown, since that requires decrypting captured text and must never be
+/// persisted at rest in plaintext just to make replay cheaper.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AggregateState {
+    total_keystrokes: u64,
+    per_hour: HashMap<i64, u64>,
+}
+
+/// An exported op row for the sync transport: the sort/dedup key, the
+/// cleartext counters, and the (possibly encrypted) text payload. The
+/// transport never needs to decrypt `text_payload`, only move it to another
+/// device's `ops` table via [`SqliteStorage::import_ops`].
+#[derive(Debug, Clone)]
+pub struct OpRecord {
+    pub op_id: String,
+    pub key_count: u32,
+    pub hour_bucket: i64,
+    pub text_nonce: Option<Vec<u8>>,
+    pub text_payload: Vec<u8>,
+}
+
+/// Generates hybrid logical timestamps: wall-clock millis, a monotonic
+/// counter that advances whenever the clock doesn't, and (by the caller)
+/// a per-device suffix. Formatted as zero-padded text so op ids sort
+/// lexicographically in timestamp order.
+struct HlcState {
+    last_millis: i64,
+    counter: u64,
+}
+
+/// One step in the schema's evolution, bringing the database to
+/// `target_version`. Most migrations are plain SQL; a migration that needs
+/// to reshape or backfill existing rows (not just add structure) can use
+/// [`MigrationStep::Fn`] instead.
+struct Migration {
+    target_version: i64,
+    step: MigrationStep,
+}
+
+enum MigrationStep {
+    Sql(&'static str),
+    Fn(fn(&Connection) -> rusqlite::Result<()>),
+}
+
+/// Ordered schema migrations, applied in order by
+/// [`SqliteStorage::run_migrations`]. `MIGRATIONS[i].target_version` must be
+/// strictly increasing; the current on-disk schema (tracked via `PRAGMA
+/// user_version`) is the version of the last migration that ran against it.
+///
+/// A database that predates this migration framework never had `user_version`
+/// set, so it reads as version 0 — the same as a brand new database — even
+/// though it already has `hourly_stats`/`sessions` and the old
+/// `keys`/`windows`/`processes` tables from its own `CREATE TABLE IF NOT
+/// EXISTS` bootstrap. Migration 1 uses `IF NOT EXISTS` for `hourly_stats`
+/// and its `idx_hourly_bucket` index (unchanged shape either way) so it
+/// doesn't collide with that; migration 2
+/// reconciles `sessions` (which gained `session_state` after the legacy
+/// schema shipped) and backfills the legacy per-keystroke tables into `ops`,
+/// since that's a reshape rather than a plain `CREATE TABLE`.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        target_version: 1,
+        step: MigrationStep::Sql(
+            r#"
+        CREATE TABLE device (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            device_id TEXT NOT NULL
+        );
+
+        CREATE TABLE meta (
+            key TEXT PRIMARY KEY,
+            value BLOB NOT NULL
+        );
+
+        CREATE TABLE ops (
+            op_id TEXT PRIMARY KEY,
+            key_count INTEGER NOT NULL,
+            hour_bucket INTEGER NOT NULL,
+            text_nonce BLOB,
+            text_payload BLOB NOT NULL
+        );
+
+        CREATE TABLE checkpoints (
+            id INTEGER PRIMARY KEY,
+            up_to_op_id TEXT NOT NULL,
+            state BLOB NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS hourly_stats (
+            id INTEGER PRIMARY KEY,
+            hour_bucket INTEGER NOT NULL,
+            key_type TEXT NOT NULL,
+            count INTEGER NOT NULL DEFAULT 1,
+            UNIQUE(hour_bucket, key_type)
+        );
+
+        CREATE TABLE key_bigrams (
+            id INTEGER PRIMARY KEY,
+            hour_bucket INTEGER NOT NULL,
+            from_key TEXT NOT NULL,
+            to_key TEXT NOT NULL,
+            count INTEGER NOT NULL DEFAULT 1,
+            UNIQUE(hour_bucket, from_key, to_key)
+        );
+
+        CREATE TABLE shortcuts (
+            id INTEGER PRIMARY KEY,
+            combo TEXT NOT NULL,
+            process TEXT NOT NULL,
+            count INTEGER NOT NULL DEFAULT 1,
+            UNIQUE(combo, process)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_hourly_bucket ON hourly_stats(hour_bucket);
+        CREATE INDEX idx_bigrams_bucket ON key_bigrams(hour_bucket);
+        CREATE INDEX idx_shortcuts_combo ON shortcuts(combo);
+        "#,
+        ),
+    },
+    Migration {
+        target_version: 2,
+        step: MigrationStep::Fn(reconcile_legacy_schema),
+    },
+];
+
+/// Whether `name` exists as a table in `conn`, regardless of schema version —
+/// used to tell a legacy (pre-migration-framework) database apart from a
+/// fresh one when neither a bare `CREATE TABLE` nor `user_version` can.
+fn table_exists(conn: &Connection, name: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+fn table_has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let has_column = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+    Ok(has_column)
+}
+
+/// Brings `sessions` and the legacy `keys`/`windows`/`processes` tables in
+/// line with the `ops`-log schema. On a fresh database neither exists yet and
+/// this just creates `sessions`; on a database opened from before this
+/// migration framework existed, `sessions` already exists (without
+/// `session_state`, added after the legacy schema shipped) and the
+/// per-keystroke history lives in `keys`/`windows`/`processes` rather than
+/// `ops` — both are reconciled here rather than in migration 1's bare SQL,
+/// since neither is a plain `CREATE TABLE`.
+fn reconcile_legacy_schema(conn: &Connection) -> rusqlite::Result<()> {
+    if !table_exists(conn, "sessions")? {
+        conn.execute_batch(
+            r#"
+            CREATE TABLE sessions (
+                id INTEGER PRIMARY KEY,
+                started_at TIMESTAMP NOT NULL,
+                ended_at TIMESTAMP,
+                key_count INTEGER NOT NULL DEFAULT 0,
+                session_state BLOB
+            );
+            "#,
+        )?;
+    } else if !table_has_column(conn, "sessions", "session_state")? {
+        conn.execute_batch("ALTER TABLE sessions ADD COLUMN session_state BLOB;")?;
+    }
+
+    if !table_exists(conn, "keys")? {
+        return Ok(());
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT k.id, k.key_count, k.started_at, w.title, p.name
+         FROM keys k
+         JOIN windows w ON k.window_id = w.id
+         JOIN processes p ON w.process_id = p.id
+         ORDER BY k.id ASC",
+    )?;
+    let legacy_rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, u32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(stmt);
+
+    for (id, key_count, started_at, window_title, process_name) in legacy_rows {
+        let millis = chrono::DateTime::parse_from_rfc3339(&started_at)
+            .map(|dt| dt.timestamp_millis())
+            .unwrap_or(0)
+            .max(0);
+        // No device had minted an op id yet when this row was recorded, so
+        // there's no device suffix to reuse; "legacy" just needs to not
+        // collide with a real device id, and the row's own (unique,
+        // insertion-ordered) id keeps rows from the same millisecond distinct.
+        let op_id = format!("{millis:013}-{id:010}-legacy");
+        let hour_bucket = millis / 1000 / 3600;
+        let payload = rmp_serde::to_vec(&TextFields {
+            process: process_name,
+            window: window_title,
+        })
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        // Legacy rows were never encrypted, so this matches `encode_text`'s
+        // own no-cipher case: no nonce, plain msgpack payload.
+        conn.execute(
+            "INSERT OR IGNORE INTO ops (op_id, key_count, hour_bucket, text_nonce, text_payload)
+             VALUES (?1, ?2, ?3, NULL, ?4)",
+            rusqlite::params![op_id, key_count, hour_bucket, payload],
+        )?;
+    }
+
+    conn.execute_batch("DROP TABLE keys; DROP TABLE windows; DROP TABLE processes;")?;
+    Ok(())
+}
 
 pub struct SqliteStorage {
     conn: Connection,
+    device_id: String,
+    hlc: Mutex<HlcState>,
+    /// Derived from the user's passphrase via Argon2, if one was supplied.
+    cipher: Option<XChaCha20Poly1305>,
+    /// Set once this database has ever had encryption enabled, whether or
+    /// not a passphrase was supplied to open it this time.
+    requires_passphrase: bool,
+    /// Source of wall-clock/monotonic time for op ids, hour buckets, and
+    /// session timestamps. `SystemClocks` in production; swappable for a
+    /// `SimulatedClocks` in tests that need deterministic rollover.
+    clocks: Arc<dyn Clocks>,
+    /// Serializes concurrent [`snapshot_to`](Self::snapshot_to) calls
+    /// against each other; SQLite's own locking keeps a snapshot consistent
+    /// against concurrent writers.
+    snapshot_lock: Mutex<()>,
 }
 
 impl SqliteStorage {
     /// Create a new storage instance with the given database path
     pub fn new(path: &Path) -> Result<Self> {
-        let conn = Connection::open(path)?;
-        let storage = Self { conn };
-        storage.init_schema()?;
-        Ok(storage)
+        Self::open(path, None, Arc::new(SystemClocks)).map_err(to_sqlite_err)
+    }
+
+    /// Open (or initialize) an encrypted database, deriving the AEAD key
+    /// from `passphrase` via Argon2. If this database has never been
+    /// encrypted before, encryption is enabled on it now with a fresh
+    /// random salt.
+    pub fn new_with_passphrase(path: &Path, passphrase: &str) -> Result<Self, StorageError> {
+        Self::open(path, Some(passphrase), Arc::new(SystemClocks))
+    }
+
+    fn open(
+        path: &Path,
+        passphrase: Option<&str>,
+        clocks: Arc<dyn Clocks>,
+    ) -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open(path)?, passphrase, clocks)
     }
 
     /// Create in-memory database for testing
     pub fn in_memory() -> Result<Self> {
-        let conn = Connection::open_in_memory()?;
-        let storage = Self { conn };
-        storage.init_schema()?;
+        Self::from_connection(Connection::open_in_memory()?, None, Arc::new(SystemClocks))
+            .map_err(to_sqlite_err)
+    }
+
+    /// Create an encrypted in-memory database for testing
+    pub fn in_memory_with_passphrase(passphrase: &str) -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open_in_memory()?, Some(passphrase), Arc::new(SystemClocks))
+    }
+
+    /// Create an in-memory database driven by an injected clock, so tests
+    /// can fast-forward op ids, hour buckets, and session timestamps
+    /// deterministically.
+    #[cfg(test)]
+    fn in_memory_with_clocks(clocks: Arc<dyn Clocks>) -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open_in_memory()?, None, clocks)
+    }
+
+    fn from_connection(
+        conn: Connection,
+        passphrase: Option<&str>,
+        clocks: Arc<dyn Clocks>,
+    ) -> Result<Self, StorageError> {
+        let mut storage = Self {
+            conn,
+            device_id: String::new(),
+            hlc: Mutex::new(HlcState {
+                last_millis: 0,
+                counter: 0,
+            }),
+            cipher: None,
+            requires_passphrase: false,
+            clocks,
+            snapshot_lock: Mutex::new(()),
+        };
+        // WAL mode lets `snapshot_to`'s `VACUUM INTO` read a consistent
+        // point-in-time view without blocking concurrent keystroke writes.
+        storage
+            .conn
+            .query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        Self::run_migrations(&storage.conn)?;
+        storage.device_id = storage.load_or_create_device_id()?;
+        storage.init_encryption(passphrase)?;
         Ok(storage)
     }
 
-    /// Initialize database schema
-    fn init_schema(&self) -> Result<()> {
-        self.conn.execute_batch(
-            r#"
-            CREATE TABLE IF NOT EXISTS processes (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL UNIQUE
-            );
+    /// Bring `conn`'s schema from its current `PRAGMA user_version` up to
+    /// [`MIGRATIONS`]'s latest version, applying every pending step in
+    /// order inside its own transaction. Refuses (rather than silently
+    /// guessing) if the on-disk version is newer than this binary
+    /// understands — that means the database was last written by a newer
+    /// release and rolling it back isn't safe.
+    fn run_migrations(conn: &Connection) -> Result<(), StorageError> {
+        let current_version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let supported = MIGRATIONS.last().map_or(0, |m| m.target_version);
 
-            CREATE TABLE IF NOT EXISTS windows (
-                id INTEGER PRIMARY KEY,
-                process_id INTEGER NOT NULL REFERENCES processes(id),
-                title TEXT NOT NULL,
-                UNIQUE(process_id, title)
-            );
+        if current_version > supported {
+            return Err(StorageError::UnsupportedSchemaVersion {
+                on_disk: current_version,
+                supported,
+            });
+        }
 
-            CREATE TABLE IF NOT EXISTS keys (
-                id INTEGER PRIMARY KEY,
-                window_id INTEGER NOT NULL REFERENCES windows(id),
-                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                key_text TEXT,
-                key_count INTEGER NOT NULL,
-                started_at TIMESTAMP NOT NULL
-            );
+        for migration in MIGRATIONS
+            .iter()
+            .filter(|m| m.target_version > current_version)
+        {
+            conn.execute_batch("BEGIN")?;
+            let applied = (|| -> Result<(), StorageError> {
+                match migration.step {
+                    MigrationStep::Sql(sql) => conn.execute_batch(sql)?,
+                    MigrationStep::Fn(f) => f(conn)?,
+                }
+                conn.pragma_update(None, "user_version", migration.target_version)?;
+                Ok(())
+            })();
 
-            CREATE TABLE IF NOT EXISTS hourly_stats (
-                id INTEGER PRIMARY KEY,
-                hour_bucket INTEGER NOT NULL,
-                key_type TEXT NOT NULL,
-                count INTEGER NOT NULL DEFAULT 1,
-                UNIQUE(hour_bucket, key_type)
-            );
+            match applied {
+                Ok(()) => conn.execute_batch("COMMIT")?,
+                Err(e) => {
+                    conn.execute_batch("ROLLBACK")?;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
 
-            CREATE TABLE IF NOT EXISTS sessions (
-                id INTEGER PRIMARY KEY,
-                started_at TIMESTAMP NOT NULL,
-                ended_at TIMESTAMP,
-                key_count INTEGER NOT NULL DEFAULT 0
-            );
+    /// Load this database's device id, minting and persisting a new one on
+    /// first use. Stable across restarts so op ids from the same machine
+    /// keep sorting and deduplicating correctly after a merge.
+    fn load_or_create_device_id(&self) -> Result<String> {
+        if let Some(id) = self
+            .conn
+            .query_row("SELECT device_id FROM device WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .optional()?
+        {
+            return Ok(id);
+        }
 
-            CREATE INDEX IF NOT EXISTS idx_keys_window ON keys(window_id);
-            CREATE INDEX IF NOT EXISTS idx_keys_created ON keys(created_at);
-            CREATE INDEX IF NOT EXISTS idx_windows_process ON windows(process_id);
-            CREATE INDEX IF NOT EXISTS idx_hourly_bucket ON hourly_stats(hour_bucket);
-            "#,
+        let id = generate_device_id();
+        self.conn.execute(
+            "INSERT INTO device (id, device_id) VALUES (1, ?1)",
+            [&id],
+        )?;
+        Ok(id)
+    }
+
+    /// Derive (or mint) this database's encryption key from `passphrase`,
+    /// if one was given, and record whether the database is gated behind a
+    /// passphrase going forward.
+    fn init_encryption(&mut self, passphrase: Option<&str>) -> Result<(), StorageError> {
+        let stored_salt: Option<Vec<u8>> = self
+            .conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'encryption_salt'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        match (stored_salt, passphrase) {
+            (Some(salt), Some(passphrase)) => {
+                self.cipher = Some(derive_cipher(passphrase, &salt)?);
+                self.requires_passphrase = true;
+            }
+            // Encrypted database, no passphrase: stay open in degraded mode.
+            // Cleartext counters (get_total_keystrokes, ...) keep working;
+            // anything that needs captured text returns PassphraseRequired.
+            (Some(_), None) => {
+                self.requires_passphrase = true;
+            }
+            // First time this database has seen a passphrase: turn on
+            // encryption with a fresh random salt.
+            (None, Some(passphrase)) => {
+                let mut salt = [0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                self.conn.execute(
+                    "INSERT INTO meta (key, value) VALUES ('encryption_salt', ?1)",
+                    [salt.to_vec()],
+                )?;
+                self.cipher = Some(derive_cipher(passphrase, &salt)?);
+                self.requires_passphrase = true;
+            }
+            (None, None) => {}
+        }
+        Ok(())
+    }
+
+    /// Mint the next hybrid logical timestamp for this device: wall-clock
+    /// millis plus a monotonic counter (so two ops in the same millisecond
+    /// still sort and dedupe correctly), suffixed with the device id to
+    /// make it globally unique across a merge.
+    fn next_op_id(&self) -> String {
+        let mut hlc = self.hlc.lock().unwrap();
+        let now_millis = self.clocks.now().timestamp_millis();
+        if now_millis > hlc.last_millis {
+            hlc.last_millis = now_millis;
+            hlc.counter = 0;
+        } else {
+            hlc.counter += 1;
+        }
+        format!(
+            "{:013}-{:010}-{}",
+            hlc.last_millis, hlc.counter, self.device_id
         )
     }
 
-    /// Record a keystroke event
-    pub fn record_keystroke(
+    /// Encrypt (if a cipher is configured) or msgpack-encode (otherwise) the
+    /// process/window text for an op, bound to `op_id` as AEAD associated
+    /// data so a ciphertext can't be replayed under a different row.
+    fn encode_text(
         &self,
+        op_id: &str,
         process: &str,
         window: &str,
-        key_count: u32,
-    ) -> Result<i64> {
-        // Get or create process
-        self.conn.execute(
-            "INSERT OR IGNORE INTO processes (name) VALUES (?1)",
-            [process],
-        )?;
-        let process_id: i64 = self.conn.query_row(
-            "SELECT id FROM processes WHERE name = ?1",
-            [process],
+    ) -> Result<(Option<Vec<u8>>, Vec<u8>), StorageError> {
+        let plain = rmp_serde::to_vec(&TextFields {
+            process: process.to_string(),
+            window: window.to_string(),
+        })?;
+
+        match &self.cipher {
+            Some(cipher) => {
+                let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+                let ciphertext = cipher
+                    .encrypt(
+                        &nonce,
+                        Payload {
+                            msg: &plain,
+                            aad: op_id.as_bytes(),
+                        },
+                    )
+                    .map_err(|_| StorageError::Crypto)?;
+                Ok((Some(nonce.to_vec()), ciphertext))
+            }
+            None => Ok((None, plain)),
+        }
+    }
+
+    /// Inverse of [`encode_text`](Self::encode_text). Returns `Ok(None)`
+    /// (rather than an error) when the row is encrypted but no passphrase
+    /// was supplied, so callers that only need cleartext counters can keep
+    /// going; callers that need the text itself should treat `None` as
+    /// [`StorageError::PassphraseRequired`].
+    fn decode_text(
+        &self,
+        op_id: &str,
+        nonce: Option<Vec<u8>>,
+        payload: &[u8],
+    ) -> Result<Option<TextFields>, StorageError> {
+        match nonce {
+            Some(nonce) => match &self.cipher {
+                Some(cipher) => {
+                    let plain = cipher
+                        .decrypt(
+                            XNonce::from_slice(&nonce),
+                            Payload {
+                                msg: payload,
+                                aad: op_id.as_bytes(),
+                            },
+                        )
+                        .map_err(|_| StorageError::Crypto)?;
+                    Ok(Some(rmp_serde::from_slice(&plain)?))
+                }
+                None => Ok(None),
+            },
+            None => Ok(Some(rmp_serde::from_slice(payload)?)),
+        }
+    }
+
+    /// Fold the latest checkpoint (if any) with every op's cleartext
+    /// counters recorded after it, in op-id order.
+    fn current_state(&self) -> Result<AggregateState, StorageError> {
+        let checkpoint: Option<(String, Vec<u8>)> = self
+            .conn
+            .query_row(
+                "SELECT up_to_op_id, state FROM checkpoints ORDER BY id DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let (cursor, mut state): (String, AggregateState) = match checkpoint {
+            Some((up_to, bytes)) => (up_to, rmp_serde::from_slice(&bytes)?),
+            None => (String::new(), AggregateState::default()),
+        };
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key_count, hour_bucket FROM ops WHERE op_id > ?1 ORDER BY op_id ASC")?;
+        let rows = stmt.query_map([&cursor], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            let (key_count, hour_bucket) = row?;
+            let key_count = key_count as u64;
+            state.total_keystrokes += key_count;
+            *state.per_hour.entry(hour_bucket).or_insert(0) += key_count;
+        }
+        Ok(state)
+    }
+
+    /// Fold a fresh checkpoint every `KEEP_STATE_EVERY` ops, tagged with the
+    /// newest op id it covers. Only ever covers the cleartext counters, so
+    /// this is safe to run whether or not a passphrase is available.
+    fn checkpoint_if_due(&self) -> Result<(), StorageError> {
+        let pending: u64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM ops
+             WHERE op_id > (SELECT COALESCE(MAX(up_to_op_id), '') FROM checkpoints)",
+            [],
             |row| row.get(0),
         )?;
+        if pending == 0 || pending % KEEP_STATE_EVERY != 0 {
+            return Ok(());
+        }
 
-        // Get or create window
+        let state = self.current_state()?;
+        let up_to_op_id: String =
+            self.conn
+                .query_row("SELECT MAX(op_id) FROM ops", [], |row| row.get(0))?;
+        let bytes = rmp_serde::to_vec(&state)?;
         self.conn.execute(
-            "INSERT OR IGNORE INTO windows (process_id, title) VALUES (?1, ?2)",
-            rusqlite::params![process_id, window],
+            "INSERT INTO checkpoints (up_to_op_id, state) VALUES (?1, ?2)",
+            rusqlite::params![up_to_op_id, bytes],
         )?;
-        let window_id: i64 = self.conn.query_row(
-            "SELECT id FROM windows WHERE process_id = ?1 AND title = ?2",
-            rusqlite::params![process_id, window],
-            |row| row.get(0),
+        Ok(())
+    }
+
+    /// Decrypt (or decode) every op's process/window text and fold it into
+    /// per-process/per-window totals. Unlike [`current_state`](Self::current_state)
+    /// this always does a full scan, since the result is never checkpointed.
+    fn text_breakdown(
+        &self,
+    ) -> Result<(HashMap<String, u64>, HashMap<(String, String), u64>), StorageError> {
+        let mut per_process = HashMap::new();
+        let mut per_window = HashMap::new();
+
+        let mut stmt = self.conn.prepare(
+            "SELECT op_id, key_count, text_nonce, text_payload FROM ops ORDER BY op_id ASC",
         )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u32>(1)?,
+                row.get::<_, Option<Vec<u8>>>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+            ))
+        })?;
+
+        for row in rows {
+            let (op_id, key_count, nonce, payload) = row?;
+            let fields = self
+                .decode_text(&op_id, nonce, &payload)?
+                .ok_or(StorageError::PassphraseRequired)?;
+            let key_count = key_count as u64;
+            *per_process.entry(fields.process.clone()).or_insert(0) += key_count;
+            *per_window
+                .entry((fields.process, fields.window))
+                .or_insert(0) += key_count;
+        }
+        Ok((per_process, per_window))
+    }
+
+    /// Record a keystroke event by appending an op to the log (rather than
+    /// mutating aggregates in place), so multiple devices' logs can later be
+    /// merged deterministically. Returns the op's rowid. If encryption is
+    /// enabled on this database, `process`/`window` are sealed before being
+    /// written.
+    pub fn record_keystroke(&self, process: &str, window: &str, key_count: u32) -> Result<i64> {
+        if self.requires_passphrase && self.cipher.is_none() {
+            return Err(to_sqlite_err(StorageError::PassphraseRequired));
+        }
+
+        let op_id = self.next_op_id();
+        let hour_bucket = self.clocks.now().timestamp() / 3600;
+        let (text_nonce, text_payload) = self
+            .encode_text(&op_id, process, window)
+            .map_err(to_sqlite_err)?;
 
-        // Insert key record
         self.conn.execute(
-            "INSERT INTO keys (window_id, key_count, started_at) VALUES (?1, ?2, ?3)",
-            rusqlite::params![window_id, key_count, Utc::now().to_rfc3339()],
+            "INSERT INTO ops (op_id, key_count, hour_bucket, text_nonce, text_payload)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![op_id, key_count, hour_bucket, text_nonce, text_payload],
         )?;
+        let rowid = self.conn.last_insert_rowid();
 
-        Ok(self.conn.last_insert_rowid())
+        self.checkpoint_if_due().map_err(to_sqlite_err)?;
+        Ok(rowid)
     }
 
-    /// Get total keystrokes
+    /// Get total keystrokes. Works even without a passphrase: the counter
+    /// is never encrypted.
     pub fn get_total_keystrokes(&self) -> Result<u64> {
-        self.conn.query_row(
-            "SELECT COALESCE(SUM(key_count), 0) FROM keys",
-            [],
-            |row| row.get(0),
-        )
+        Ok(self.current_state().map_err(to_sqlite_err)?.total_keystrokes)
+    }
+
+    /// Sum `key_count` across ops matching a caller-built `WHERE` predicate
+    /// over cleartext `ops` columns (currently just `hour_bucket`). `params`
+    /// are bound positionally as `?` placeholders; `predicate_sql` must
+    /// never itself embed a caller-supplied value, only column/operator
+    /// tokens the caller already validated. Works without a passphrase: it
+    /// only ever touches `hour_bucket` and `key_count`, never the encrypted
+    /// text columns.
+    pub fn count_keys_matching(
+        &self,
+        predicate_sql: &str,
+        params: &[rusqlite::types::Value],
+    ) -> Result<u64> {
+        let sql = format!("SELECT COALESCE(SUM(key_count), 0) FROM ops WHERE {predicate_sql}");
+        self.conn
+            .query_row(&sql, rusqlite::params_from_iter(params), |row| row.get(0))
     }
 
-    /// Get keystrokes by process
+    /// Get keystrokes by process. Requires the passphrase if this database
+    /// is encrypted, since process names are the captured text being
+    /// protected.
     pub fn get_keystrokes_by_process(&self) -> Result<Vec<(String, u64)>> {
+        let (per_process, _) = self.text_breakdown().map_err(to_sqlite_err)?;
+        let mut rows: Vec<_> = per_process.into_iter().collect();
+        rows.sort_by(|a, b| b.1.cmp(&a.1));
+        Ok(rows)
+    }
+
+    /// Get keystrokes grouped by window (process, title, total). Requires
+    /// the passphrase if this database is encrypted.
+    pub fn get_keystrokes_by_window(&self) -> Result<Vec<(String, String, u64)>> {
+        let (_, per_window) = self.text_breakdown().map_err(to_sqlite_err)?;
+        let mut rows: Vec<_> = per_window
+            .into_iter()
+            .map(|((process, title), total)| (process, title, total))
+            .collect();
+        rows.sort_by(|a, b| b.2.cmp(&a.2));
+        Ok(rows)
+    }
+
+    /// Export every op recorded strictly after `since_op_id` (pass `""` to
+    /// export the whole log), for a sync transport to ship to another
+    /// device's [`import_ops`](Self::import_ops). Encrypted text travels as
+    /// opaque ciphertext; the transport never decrypts it.
+    pub fn export_ops_since(&self, since_op_id: &str) -> Result<Vec<OpRecord>, StorageError> {
         let mut stmt = self.conn.prepare(
-            "SELECT p.name, SUM(k.key_count) as total
-             FROM keys k
-             JOIN windows w ON k.window_id = w.id
-             JOIN processes p ON w.process_id = p.id
-             GROUP BY p.id
-             ORDER BY total DESC",
+            "SELECT op_id, key_count, hour_bucket, text_nonce, text_payload
+             FROM ops WHERE op_id > ?1 ORDER BY op_id ASC",
         )?;
+        let rows = stmt
+            .query_map([since_op_id], |row| {
+                Ok(OpRecord {
+                    op_id: row.get(0)?,
+                    key_count: row.get(1)?,
+                    hour_bucket: row.get(2)?,
+                    text_nonce: row.get(3)?,
+                    text_payload: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
 
-        let rows = stmt.query_map([], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
-        })?;
+    /// Merge another device's exported ops into this log. Ops are
+    /// deduplicated by `op_id`, and every op is an idempotent additive
+    /// increment, so the resulting aggregate state is deterministic
+    /// regardless of which device imports from which, or how many times.
+    /// Imported text stays exactly as encrypted (or not) by the exporting
+    /// device; merging two databases with different passphrases is not
+    /// supported.
+    pub fn import_ops<I: IntoIterator<Item = OpRecord>>(&self, ops: I) -> Result<(), StorageError> {
+        let mut earliest_imported_op_id: Option<String> = None;
+        for op in ops {
+            if earliest_imported_op_id
+                .as_ref()
+                .map_or(true, |earliest| &op.op_id < earliest)
+            {
+                earliest_imported_op_id = Some(op.op_id.clone());
+            }
+            self.conn.execute(
+                "INSERT OR IGNORE INTO ops (op_id, key_count, hour_bucket, text_nonce, text_payload)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![op.op_id, op.key_count, op.hour_bucket, op.text_nonce, op.text_payload],
+            )?;
+        }
 
-        rows.collect()
+        // Op ids from another device aren't guaranteed to sort above this
+        // device's own, e.g. if the other device's clock ran behind. Any
+        // existing checkpoint at or past the earliest imported op id was
+        // folded without that op, so it's stale; drop it and let
+        // `current_state`/`checkpoint_if_due` replay from the nearest
+        // still-valid checkpoint (or the whole log) instead of silently
+        // skipping ops that sort below the cursor.
+        if let Some(earliest) = earliest_imported_op_id {
+            self.conn.execute(
+                "DELETE FROM checkpoints WHERE up_to_op_id >= ?1",
+                [&earliest],
+            )?;
+        }
+
+        self.checkpoint_if_due()
+    }
+
+    /// Write a consistent, point-in-time copy of the live database to
+    /// `path` without interrupting capture. Backed by `VACUUM INTO`, which
+    /// (thanks to WAL mode, set up in [`from_connection`](Self::from_connection))
+    /// can proceed while this connection keeps recording keystrokes.
+    /// Concurrent calls to this method on the same `SqliteStorage` are
+    /// serialized, since `VACUUM INTO` isn't reentrant on one connection.
+    pub fn snapshot_to(&self, path: &Path) -> Result<(), StorageError> {
+        let _guard = self.snapshot_lock.lock().unwrap();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // VACUUM INTO refuses to overwrite an existing file.
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        self.conn
+            .execute("VACUUM INTO ?1", [path.to_string_lossy().to_string()])?;
+        Ok(())
     }
 
     /// Record hourly aggregate for heatmap
@@ -164,11 +852,310 @@ impl SqliteStorage {
 
         rows.collect()
     }
+
+    /// Record a use of a modifier+key shortcut, keyed by its canonical combo
+    /// rendering (e.g. `cmd+shift+z`) and the process it was used in.
+    pub fn record_shortcut(&self, combo: &str, process: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO shortcuts (combo, process, count) VALUES (?1, ?2, 1)
+             ON CONFLICT(combo, process) DO UPDATE SET count = count + 1",
+            rusqlite::params![combo, process],
+        )?;
+        Ok(())
+    }
+
+    /// Most-used shortcuts overall
+    pub fn get_top_shortcuts(&self, limit: usize) -> Result<Vec<(String, u64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT combo, SUM(count) as total
+             FROM shortcuts
+             GROUP BY combo
+             ORDER BY total DESC
+             LIMIT ?1",
+        )?;
+
+        let rows = stmt.query_map([limit as i64], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+        })?;
+
+        rows.collect()
+    }
+
+    /// Most-used shortcuts within a single process
+    pub fn get_top_shortcuts_by_process(
+        &self,
+        process: &str,
+        limit: usize,
+    ) -> Result<Vec<(String, u64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT combo, count
+             FROM shortcuts
+             WHERE process = ?1
+             ORDER BY count DESC
+             LIMIT ?2",
+        )?;
+
+        let rows = stmt.query_map(rusqlite::params![process, limit as i64], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+        })?;
+
+        rows.collect()
+    }
+
+    /// Record a key-to-key transition for the bigram digraph export
+    pub fn record_bigram(&self, hour_bucket: i64, from: &str, to: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO key_bigrams (hour_bucket, from_key, to_key, count) VALUES (?1, ?2, ?3, 1)
+             ON CONFLICT(hour_bucket, from_key, to_key) DO UPDATE SET count = count + 1",
+            rusqlite::params![hour_bucket, from, to],
+        )?;
+        Ok(())
+    }
+
+    /// Get aggregated bigram counts, optionally restricted to hour buckets >= `since_hour_bucket`
+    pub fn get_bigrams(&self, since_hour_bucket: Option<i64>) -> Result<Vec<(String, String, u64)>> {
+        let mut stmt = match since_hour_bucket {
+            Some(_) => self.conn.prepare(
+                "SELECT from_key, to_key, SUM(count) as total
+                 FROM key_bigrams
+                 WHERE hour_bucket >= ?1
+                 GROUP BY from_key, to_key
+                 ORDER BY total DESC",
+            )?,
+            None => self.conn.prepare(
+                "SELECT from_key, to_key, SUM(count) as total
+                 FROM key_bigrams
+                 GROUP BY from_key, to_key
+                 ORDER BY total DESC",
+            )?,
+        };
+
+        let map_row = |row: &rusqlite::Row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u64>(2)?,
+            ))
+        };
+
+        let rows = match since_hour_bucket {
+            Some(cutoff) => stmt.query_map([cutoff], map_row)?.collect(),
+            None => stmt.query_map([], map_row)?.collect(),
+        };
+
+        rows
+    }
+
+    /// Start a new capture session, returning its id so the daemon can tie
+    /// subsequent snapshots and the final `end_session` call to it.
+    pub fn start_session(&self) -> Result<SessionId> {
+        self.conn.execute(
+            "INSERT INTO sessions (started_at, key_count) VALUES (?1, 0)",
+            [self.clocks.now().to_rfc3339()],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Mark a session as cleanly ended.
+    pub fn end_session(&self, session_id: SessionId, key_count: u64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE sessions SET ended_at = ?1, key_count = ?2 WHERE id = ?3",
+            rusqlite::params![self.clocks.now().to_rfc3339(), key_count, session_id],
+        )?;
+        Ok(())
+    }
+
+    /// Periodically snapshot a session's in-flight aggregation state as
+    /// msgpack, so a crash or unclean exit loses at most the events since
+    /// the last snapshot instead of the whole session.
+    pub fn save_session_state<T: Serialize>(
+        &self,
+        session_id: SessionId,
+        state: &T,
+    ) -> Result<(), StorageError> {
+        let bytes = rmp_serde::to_vec(state)?;
+        self.conn.execute(
+            "UPDATE sessions SET session_state = ?1 WHERE id = ?2",
+            rusqlite::params![bytes, session_id],
+        )?;
+        Ok(())
+    }
+
+    /// Find the most recent session that was never cleanly ended (its
+    /// `ended_at` is NULL) and deserialize its last snapshotted state, if
+    /// one was ever saved.
+    pub fn resume_latest_session<T: DeserializeOwned>(
+        &self,
+    ) -> Result<Option<(SessionId, T)>, StorageError> {
+        let row: Option<(SessionId, Option<Vec<u8>>)> = self
+            .conn
+            .query_row(
+                "SELECT id, session_state FROM sessions
+                 WHERE ended_at IS NULL
+                 ORDER BY id DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let Some((session_id, state_blob)) = row else {
+            return Ok(None);
+        };
+
+        match state_blob {
+            Some(bytes) => Ok(Some((session_id, rmp_serde::from_slice(&bytes)?))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Derive a 256-bit AEAD key from a user passphrase and a stored salt via
+/// Argon2 (memory-hard, so offline brute-forcing the passphrase is
+/// expensive even if the database file leaks).
+fn derive_cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, StorageError> {
+    let mut key_bytes = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key_bytes)
+        .map_err(|e| StorageError::KeyDerivation(e.to_string()))?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&key_bytes)))
+}
+
+/// Mint a process- and time-derived device id on first use. Not
+/// cryptographically random, just unique enough to keep per-device op-id
+/// suffixes from colliding.
+fn generate_device_id() -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let mut hasher = DefaultHasher::new();
+    std::process::id().hash(&mut hasher);
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Delete all but the `keep` most recently written snapshot files in `dir`.
+/// Snapshot files are named with a fixed-width UTC timestamp (see
+/// `Daemon::maybe_snapshot`), so sorting file names lexicographically is
+/// equivalent to sorting by age.
+pub fn rotate_snapshots(dir: &Path, keep: usize) -> Result<(), StorageError> {
+    let mut snapshots: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "db"))
+        .collect();
+    snapshots.sort();
+
+    if snapshots.len() > keep {
+        for stale in &snapshots[..snapshots.len() - keep] {
+            std::fs::remove_file(stale)?;
+        }
+    }
+    Ok(())
+}
+
+/// Common surface for a keystroke-data backend: recording events, reading
+/// back aggregates, and taking a portable snapshot. Lets callers (the CLI,
+/// the daemon, export tooling) depend on this interface instead of
+/// [`SqliteStorage`] directly, so an alternate store can be substituted
+/// without touching them.
+pub trait KeystrokeStore: Send {
+    fn record_keystroke(&self, process: &str, window: &str, key_count: u32) -> Result<i64>;
+    fn get_total_keystrokes(&self) -> Result<u64>;
+    fn get_keystrokes_by_process(&self) -> Result<Vec<(String, u64)>>;
+    fn record_hourly_stat(&self, hour_bucket: i64, key_type: &str) -> Result<()>;
+    fn get_heatmap_data(&self) -> Result<Vec<(String, u64)>>;
+
+    /// Write a consistent, point-in-time copy of the store to `path`
+    /// without interrupting capture.
+    fn snapshot_to(&self, path: &Path) -> Result<(), StorageError>;
+}
+
+impl KeystrokeStore for SqliteStorage {
+    fn record_keystroke(&self, process: &str, window: &str, key_count: u32) -> Result<i64> {
+        SqliteStorage::record_keystroke(self, process, window, key_count)
+    }
+
+    fn get_total_keystrokes(&self) -> Result<u64> {
+        SqliteStorage::get_total_keystrokes(self)
+    }
+
+    fn get_keystrokes_by_process(&self) -> Result<Vec<(String, u64)>> {
+        SqliteStorage::get_keystrokes_by_process(self)
+    }
+
+    fn record_hourly_stat(&self, hour_bucket: i64, key_type: &str) -> Result<()> {
+        SqliteStorage::record_hourly_stat(self, hour_bucket, key_type)
+    }
+
+    fn get_heatmap_data(&self) -> Result<Vec<(String, u64)>> {
+        SqliteStorage::get_heatmap_data(self)
+    }
+
+    fn snapshot_to(&self, path: &Path) -> Result<(), StorageError> {
+        SqliteStorage::snapshot_to(self, path)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::SimulatedClocks;
+    use std::time::Duration;
+
+    fn simulated_clocks() -> Arc<SimulatedClocks> {
+        let start = chrono::DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        Arc::new(SimulatedClocks::new(start))
+    }
+
+    #[test]
+    fn test_record_keystroke_lands_in_the_hour_bucket_at_record_time() {
+        let clocks = simulated_clocks();
+        let storage = SqliteStorage::in_memory_with_clocks(clocks.clone()).unwrap();
+
+        storage.record_keystroke("VSCode", "main.rs", 10).unwrap();
+        // Cross an hour boundary before recording the second keystroke.
+        clocks.advance(Duration::from_secs(3600));
+        storage.record_keystroke("VSCode", "main.rs", 5).unwrap();
+
+        let ops = storage.export_ops_since("").unwrap();
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[1].hour_bucket, ops[0].hour_bucket + 1);
+    }
+
+    #[test]
+    fn test_count_keys_matching_binds_parameters_instead_of_interpolating() {
+        let clocks = simulated_clocks();
+        let storage = SqliteStorage::in_memory_with_clocks(clocks.clone()).unwrap();
+
+        storage.record_keystroke("VSCode", "main.rs", 10).unwrap();
+        clocks.advance(Duration::from_secs(3600));
+        storage.record_keystroke("VSCode", "main.rs", 5).unwrap();
+
+        let first_hour = storage.export_ops_since("").unwrap()[0].hour_bucket;
+
+        let total = storage
+            .count_keys_matching(
+                "hour_bucket >= ?",
+                &[rusqlite::types::Value::Integer(first_hour + 1)],
+            )
+            .unwrap();
+        assert_eq!(total, 5);
+
+        let total = storage
+            .count_keys_matching(
+                "hour_bucket = ?",
+                &[rusqlite::types::Value::Integer(first_hour)],
+            )
+            .unwrap();
+        assert_eq!(total, 10);
+    }
 
     #[test]
     fn test_in_memory_storage() {
@@ -213,4 +1200,387 @@ mod tests {
         assert_eq!(by_process[0], ("VSCode".to_string(), 150));
         assert_eq!(by_process[1], ("Terminal".to_string(), 30));
     }
+
+    #[test]
+    fn test_keystrokes_by_window() {
+        let storage = SqliteStorage::in_memory().unwrap();
+
+        storage.record_keystroke("VSCode", "main.rs", 10).unwrap();
+        storage.record_keystroke("VSCode", "lib.rs", 5).unwrap();
+
+        let by_window = storage.get_keystrokes_by_window().unwrap();
+        assert_eq!(by_window.len(), 2);
+        assert!(by_window.contains(&("VSCode".to_string(), "main.rs".to_string(), 10)));
+        assert!(by_window.contains(&("VSCode".to_string(), "lib.rs".to_string(), 5)));
+    }
+
+    #[test]
+    fn test_shortcut_recording_and_lookup() {
+        let storage = SqliteStorage::in_memory().unwrap();
+
+        storage.record_shortcut("cmd+shift+z", "VSCode").unwrap();
+        storage.record_shortcut("cmd+shift+z", "VSCode").unwrap();
+        storage.record_shortcut("cmd+c", "Terminal").unwrap();
+
+        let top = storage.get_top_shortcuts(10).unwrap();
+        assert_eq!(top[0], ("cmd+shift+z".to_string(), 2));
+
+        let vscode_only = storage.get_top_shortcuts_by_process("VSCode", 10).unwrap();
+        assert_eq!(vscode_only, vec![("cmd+shift+z".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_bigram_recording_and_lookup() {
+        let storage = SqliteStorage::in_memory().unwrap();
+
+        storage.record_bigram(100, "e", "r").unwrap();
+        storage.record_bigram(100, "e", "r").unwrap();
+        storage.record_bigram(101, "r", "t").unwrap();
+
+        let all = storage.get_bigrams(None).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0], ("e".to_string(), "r".to_string(), 2));
+
+        let recent = storage.get_bigrams(Some(101)).unwrap();
+        assert_eq!(recent, vec![("r".to_string(), "t".to_string(), 1)]);
+    }
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct TestSnapshot {
+        total_keystrokes: u64,
+    }
+
+    #[test]
+    fn test_resume_latest_session_roundtrips_state() {
+        let storage = SqliteStorage::in_memory().unwrap();
+
+        let session_id = storage.start_session().unwrap();
+        storage
+            .save_session_state(session_id, &TestSnapshot { total_keystrokes: 42 })
+            .unwrap();
+
+        let (resumed_id, state): (SessionId, TestSnapshot) = storage
+            .resume_latest_session()
+            .unwrap()
+            .expect("expected a resumable session");
+        assert_eq!(resumed_id, session_id);
+        assert_eq!(state, TestSnapshot { total_keystrokes: 42 });
+    }
+
+    #[test]
+    fn test_resume_latest_session_ignores_ended_sessions() {
+        let storage = SqliteStorage::in_memory().unwrap();
+
+        let session_id = storage.start_session().unwrap();
+        storage.end_session(session_id, 5).unwrap();
+
+        let resumed: Option<(SessionId, TestSnapshot)> = storage.resume_latest_session().unwrap();
+        assert!(resumed.is_none());
+    }
+
+    #[test]
+    fn test_total_keystrokes_survives_a_checkpoint() {
+        let storage = SqliteStorage::in_memory().unwrap();
+
+        let ops = KEEP_STATE_EVERY * 2 + 3;
+        for _ in 0..ops {
+            storage.record_keystroke("VSCode", "main.rs", 1).unwrap();
+        }
+
+        assert_eq!(storage.get_total_keystrokes().unwrap(), ops);
+    }
+
+    #[test]
+    fn test_export_and_import_ops_merges_across_devices() {
+        let device_a = SqliteStorage::in_memory().unwrap();
+        let device_b = SqliteStorage::in_memory().unwrap();
+
+        device_a.record_keystroke("VSCode", "main.rs", 10).unwrap();
+        device_b.record_keystroke("Terminal", "zsh", 5).unwrap();
+
+        let ops_from_b = device_b.export_ops_since("").unwrap();
+        device_a.import_ops(ops_from_b).unwrap();
+
+        assert_eq!(device_a.get_total_keystrokes().unwrap(), 15);
+        assert_eq!(
+            device_a.get_keystrokes_by_process().unwrap(),
+            vec![("VSCode".to_string(), 10), ("Terminal".to_string(), 5)]
+        );
+    }
+
+    #[test]
+    fn test_import_ops_older_than_the_latest_checkpoint_are_not_dropped() {
+        let clocks_a = simulated_clocks();
+        let device_a = SqliteStorage::in_memory_with_clocks(clocks_a.clone()).unwrap();
+
+        // Device A records enough ops to fold a checkpoint, all at a later
+        // wall-clock time than device B's op below.
+        clocks_a.advance(Duration::from_secs(3600));
+        for _ in 0..KEEP_STATE_EVERY {
+            device_a.record_keystroke("VSCode", "main.rs", 1).unwrap();
+        }
+        assert_eq!(device_a.get_total_keystrokes().unwrap(), KEEP_STATE_EVERY);
+
+        // Device B's op was recorded before device A advanced its clock, so
+        // its op id sorts below device A's checkpoint cursor.
+        let device_b = SqliteStorage::in_memory_with_clocks(simulated_clocks()).unwrap();
+        device_b.record_keystroke("Terminal", "zsh", 5).unwrap();
+
+        let ops_from_b = device_b.export_ops_since("").unwrap();
+        device_a.import_ops(ops_from_b).unwrap();
+
+        assert_eq!(
+            device_a.get_total_keystrokes().unwrap(),
+            KEEP_STATE_EVERY + 5
+        );
+        assert_eq!(
+            device_a.get_keystrokes_by_process().unwrap(),
+            vec![
+                ("VSCode".to_string(), KEEP_STATE_EVERY),
+                ("Terminal".to_string(), 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_ops_is_idempotent() {
+        let device_a = SqliteStorage::in_memory().unwrap();
+        let device_b = SqliteStorage::in_memory().unwrap();
+
+        device_b.record_keystroke("Terminal", "zsh", 5).unwrap();
+        let ops_from_b = device_b.export_ops_since("").unwrap();
+
+        device_a.import_ops(ops_from_b.clone()).unwrap();
+        device_a.import_ops(ops_from_b).unwrap();
+
+        assert_eq!(device_a.get_total_keystrokes().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_encrypted_storage_roundtrips_with_correct_passphrase() {
+        let storage = SqliteStorage::in_memory_with_passphrase("correct horse battery staple").unwrap();
+
+        storage.record_keystroke("VSCode", "main.rs", 10).unwrap();
+        storage.record_keystroke("Terminal", "zsh", 5).unwrap();
+
+        assert_eq!(storage.get_total_keystrokes().unwrap(), 15);
+        assert_eq!(
+            storage.get_keystrokes_by_process().unwrap(),
+            vec![("VSCode".to_string(), 10), ("Terminal".to_string(), 5)]
+        );
+    }
+
+    #[test]
+    fn test_totals_readable_without_passphrase_but_text_is_not() {
+        let path = std::env::temp_dir().join(format!(
+            "kstrk-encryption-test-{}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        {
+            let storage = SqliteStorage::new_with_passphrase(&path, "hunter2").unwrap();
+            storage.record_keystroke("VSCode", "secret-plan.md", 10).unwrap();
+        }
+
+        let reopened = SqliteStorage::new(&path).unwrap();
+        assert_eq!(reopened.get_total_keystrokes().unwrap(), 10);
+        assert!(reopened.get_keystrokes_by_process().is_err());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_migrations_bring_a_fresh_database_to_the_latest_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        SqliteStorage::run_migrations(&conn).unwrap();
+
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.last().unwrap().target_version);
+
+        // The v1 schema actually landed, not just the version pragma.
+        conn.execute(
+            "INSERT INTO device (id, device_id) VALUES (1, 'test-device')",
+            [],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrations_are_a_no_op_on_an_up_to_date_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        SqliteStorage::run_migrations(&conn).unwrap();
+        // Re-running must not try (and fail) to re-create existing tables.
+        SqliteStorage::run_migrations(&conn).unwrap();
+    }
+
+    #[test]
+    fn test_migrations_reconcile_a_pre_existing_legacy_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        // Mirrors the schema a pre-ops-log install bootstrapped directly via
+        // its own `CREATE TABLE IF NOT EXISTS`, before this migration
+        // framework (and `PRAGMA user_version`) existed — so it reads as
+        // version 0, same as a brand new database.
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS processes (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE
+            );
+            CREATE TABLE IF NOT EXISTS windows (
+                id INTEGER PRIMARY KEY,
+                process_id INTEGER NOT NULL REFERENCES processes(id),
+                title TEXT NOT NULL,
+                UNIQUE(process_id, title)
+            );
+            CREATE TABLE IF NOT EXISTS keys (
+                id INTEGER PRIMARY KEY,
+                window_id INTEGER NOT NULL REFERENCES windows(id),
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                key_text TEXT,
+                key_count INTEGER NOT NULL,
+                started_at TIMESTAMP NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS hourly_stats (
+                id INTEGER PRIMARY KEY,
+                hour_bucket INTEGER NOT NULL,
+                key_type TEXT NOT NULL,
+                count INTEGER NOT NULL DEFAULT 1,
+                UNIQUE(hour_bucket, key_type)
+            );
+            CREATE TABLE IF NOT EXISTS sessions (
+                id INTEGER PRIMARY KEY,
+                started_at TIMESTAMP NOT NULL,
+                ended_at TIMESTAMP,
+                key_count INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS idx_keys_window ON keys(window_id);
+            CREATE INDEX IF NOT EXISTS idx_keys_created ON keys(created_at);
+            CREATE INDEX IF NOT EXISTS idx_windows_process ON windows(process_id);
+            CREATE INDEX IF NOT EXISTS idx_hourly_bucket ON hourly_stats(hour_bucket);
+            "#,
+        )
+        .unwrap();
+        conn.execute("INSERT INTO processes (name) VALUES ('VSCode')", [])
+            .unwrap();
+        conn.execute(
+            "INSERT INTO windows (process_id, title) VALUES (1, 'main.rs')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO keys (window_id, key_count, started_at) VALUES (1, 10, '2024-01-01T00:00:00+00:00')",
+            [],
+        )
+        .unwrap();
+
+        // Must not collide on hourly_stats/sessions, and must fold the
+        // legacy keystroke history into `ops` instead of orphaning it.
+        SqliteStorage::run_migrations(&conn).unwrap();
+
+        let legacy_tables: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name IN ('keys', 'windows', 'processes')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(legacy_tables, 0);
+
+        let total: u64 = conn
+            .query_row("SELECT COALESCE(SUM(key_count), 0) FROM ops", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(total, 10);
+
+        assert!(table_has_column(&conn, "sessions", "session_state").unwrap());
+
+        // A later re-open of the now-upgraded database must be a no-op.
+        SqliteStorage::run_migrations(&conn).unwrap();
+        let total_after_rerun: u64 = conn
+            .query_row("SELECT COALESCE(SUM(key_count), 0) FROM ops", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(total_after_rerun, 10);
+    }
+
+    #[test]
+    fn test_opening_rejects_a_database_newer_than_this_binary_understands() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", 999i64).unwrap();
+
+        let err = SqliteStorage::run_migrations(&conn).unwrap_err();
+        assert!(matches!(
+            err,
+            StorageError::UnsupportedSchemaVersion { on_disk: 999, .. }
+        ));
+    }
+
+    #[test]
+    fn test_wrong_passphrase_fails_to_decrypt_text() {
+        let path = std::env::temp_dir().join(format!(
+            "kstrk-encryption-wrong-pass-{}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        {
+            let storage = SqliteStorage::new_with_passphrase(&path, "hunter2").unwrap();
+            storage.record_keystroke("VSCode", "secret-plan.md", 10).unwrap();
+        }
+
+        let wrong = SqliteStorage::new_with_passphrase(&path, "not-hunter2").unwrap();
+        assert_eq!(wrong.get_total_keystrokes().unwrap(), 10);
+        assert!(wrong.get_keystrokes_by_process().is_err());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_snapshot_to_produces_a_standalone_db_with_the_same_data() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        storage.record_keystroke("VSCode", "main.rs", 10).unwrap();
+
+        let snapshot_path = std::env::temp_dir().join(format!(
+            "kstrk-snapshot-test-{}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&snapshot_path);
+
+        storage.snapshot_to(&snapshot_path).unwrap();
+
+        let reopened = SqliteStorage::new(&snapshot_path).unwrap();
+        assert_eq!(reopened.get_total_keystrokes().unwrap(), 10);
+
+        let _ = std::fs::remove_file(&snapshot_path);
+    }
+
+    #[test]
+    fn test_rotate_snapshots_keeps_only_the_newest() {
+        let dir = std::env::temp_dir().join(format!(
+            "kstrk-snapshot-rotation-test-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for name in ["kstrk-1.db", "kstrk-2.db", "kstrk-3.db"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        rotate_snapshots(&dir, 2).unwrap();
+
+        let mut remaining: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["kstrk-2.db", "kstrk-3.db"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
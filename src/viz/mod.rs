@@ -42,6 +42,68 @@ pub fn heat_intensity(count: u64, max: u64) -> f64 {
     }
 }
 
+/// Render a key-transition digraph in Graphviz DOT format.
+///
+/// `bigrams` is `(from, to, count)` triples. When `undirected` is set, `(a, b)` and
+/// `(b, a)` are merged into a single `--` edge; otherwise each ordered pair becomes
+/// a `->` edge. Edges below `min_weight` are dropped so large graphs stay readable.
+/// Edge `penwidth` scales with transition frequency, and each node is filled with a
+/// grayscale shade keyed off its total usage via [`heat_intensity`].
+pub fn render_dot_graph(bigrams: &[(String, String, u64)], undirected: bool, min_weight: u64) -> String {
+    let (kind, edgeop) = if undirected { ("graph", "--") } else { ("digraph", "->") };
+
+    let edges: Vec<(String, String, u64)> = if undirected {
+        let mut merged: HashMap<(String, String), u64> = HashMap::new();
+        for (from, to, count) in bigrams {
+            let key = if from <= to {
+                (from.clone(), to.clone())
+            } else {
+                (to.clone(), from.clone())
+            };
+            *merged.entry(key).or_insert(0) += count;
+        }
+        merged
+            .into_iter()
+            .map(|((a, b), count)| (a, b, count))
+            .collect()
+    } else {
+        bigrams.to_vec()
+    };
+
+    let edges: Vec<_> = edges.into_iter().filter(|(_, _, count)| *count >= min_weight).collect();
+    let max_edge = edges.iter().map(|(_, _, count)| *count).max().unwrap_or(0);
+
+    let mut node_totals: HashMap<&str, u64> = HashMap::new();
+    for (from, to, count) in &edges {
+        *node_totals.entry(from.as_str()).or_insert(0) += count;
+        *node_totals.entry(to.as_str()).or_insert(0) += count;
+    }
+    let max_node = node_totals.values().copied().max().unwrap_or(0);
+
+    let mut output = format!("{} keystrokes {{\n", kind);
+
+    let mut nodes: Vec<_> = node_totals.keys().copied().collect();
+    nodes.sort_unstable();
+    for node in nodes {
+        let total = node_totals[node];
+        let shade = 1.0 - 0.6 * heat_intensity(total, max_node);
+        output.push_str(&format!(
+            "  \"{}\" [style=filled,fillcolor=\"0.000 0.000 {:.3}\"];\n",
+            node, shade
+        ));
+    }
+
+    for (from, to, count) in &edges {
+        let penwidth = 1.0 + 4.0 * heat_intensity(*count, max_edge);
+        output.push_str(&format!(
+            "  \"{}\" {} \"{}\" [weight={},penwidth={:.2},label=\"{}\"];\n",
+            from, edgeop, to, count, penwidth, count
+        ));
+    }
+    output.push_str("}\n");
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -60,4 +122,36 @@ mod tests {
         assert_eq!(heat_intensity(50, 100), 0.5);
         assert_eq!(heat_intensity(100, 100), 1.0);
     }
+
+    #[test]
+    fn test_render_dot_graph_directed() {
+        let bigrams = vec![("e".to_string(), "r".to_string(), 42)];
+        let dot = render_dot_graph(&bigrams, false, 0);
+        assert!(dot.starts_with("digraph keystrokes {\n"));
+        assert!(dot.contains("\"e\" -> \"r\" [weight=42,penwidth=5.00,label=\"42\"];"));
+    }
+
+    #[test]
+    fn test_render_dot_graph_undirected_merges_and_prunes() {
+        let bigrams = vec![
+            ("a".to_string(), "b".to_string(), 3),
+            ("b".to_string(), "a".to_string(), 2),
+            ("c".to_string(), "d".to_string(), 1),
+        ];
+        let dot = render_dot_graph(&bigrams, true, 2);
+        assert!(dot.starts_with("graph keystrokes {\n"));
+        assert!(dot.contains("\"a\" -- \"b\" [weight=5,penwidth=5.00,label=\"5\"];"));
+        assert!(!dot.contains("\"c\""));
+    }
+
+    #[test]
+    fn test_render_dot_graph_shades_nodes_by_total_usage() {
+        let bigrams = vec![
+            ("a".to_string(), "b".to_string(), 10),
+            ("c".to_string(), "d".to_string(), 5),
+        ];
+        let dot = render_dot_graph(&bigrams, false, 0);
+        assert!(dot.contains("\"a\" [style=filled,fillcolor=\"0.000 0.000 0.400\"];"));
+        assert!(dot.contains("\"c\" [style=filled,fillcolor=\"0.000 0.000 0.700\"];"));
+    }
 }